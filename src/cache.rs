@@ -2,32 +2,170 @@
 
 use sqlx::{Database, Pool};
 use std::future::Future;
-use redis::{aio::MultiplexedConnection};
 use colored::*;
 use tokio_util::sync::CancellationToken;
 use tokio::task::JoinHandle;
 use std::sync::{Arc, Mutex};
 
-use std::thread::sleep;
 use std::time::Duration;
-use redis::{Client, Connection};
+use redis::Client;
+use redis::aio::MultiplexedConnection;
 
-use crate::cache_sync;
+use crate::cache_sync::{self, CallbackOutcome, LoadCallback, SyncResource, WriteCallback};
+use crate::entity::{self, CleanTtl, Entry, Snapshot};
+use crate::inflight::InFlight;
+use crate::l1::{self, L1};
+use crate::ttl::{SharedTtlPolicy, TtlContext, TtlPolicy, MAX_TTL};
+use axum::http::response::Parts;
+use crate::middleware;
+use crate::connection::CacheConn;
+use crate::keys::KeySpace;
+use crate::lease::Leases;
+use crate::registry::CacheRegistryBuilder;
+#[cfg(feature = "tls")]
+use crate::tls::TlsConfig;
 
 use std::sync::atomic::{AtomicBool, Ordering};
 
 /// Cache system config.
 /// - `redis_url`: Redis server URL
-#[derive(Debug, Clone)]
+/// - `key_prefix`: global prefix for every key (ex: "myapp:prod:")
+/// - `dirty_marker`, `delete_marker`, `lock_marker`, `missing_marker`,
+///   `ttl_marker`, `leader_marker`: internal namespace names
+/// - `invalidation_channel`: pub/sub channel of L1 invalidations
+/// - `cluster_nodes`: Redis Cluster seed URLs (feature `cluster`)
+/// - `sentinel`: Redis Sentinel settings (feature `sentinel`)
+/// - `username`, `password`: ACL credentials, kept out of `redis_url` and logs
+/// - `tls`: TLS settings (feature `tls`)
+/// - `keyspace_events`: report evicted dirty entries through keyspace
+///   notifications (merged into the server's flags)
+/// - `eviction_check`: what to do at startup when `maxmemory-policy` may
+///   evict dirty entries
+/// - `on_dirty_evicted`: called with the entity key (ex: "posts:1") of a
+///   dirty entry Redis evicted before it was flushed (needs `keyspace_events`)
+#[derive(Clone)]
 pub struct CacheConnConfig {
     pub redis_url: String,
+    pub key_prefix: String,
+    pub dirty_marker: String,
+    pub delete_marker: String,
+    pub lock_marker: String,
+    pub missing_marker: String,
+    pub ttl_marker: String,
+    pub leader_marker: String,
+    pub invalidation_channel: String,
+    #[cfg(feature = "cluster")]
+    pub cluster_nodes: Vec<String>,
+    #[cfg(feature = "sentinel")]
+    pub sentinel: Option<SentinelConfig>,
+    pub username: Option<String>,
+    pub password: Option<Secret>,
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsConfig>,
+    pub keyspace_events: bool,
+    pub eviction_check: EvictionCheck,
+    pub on_dirty_evicted: Option<EvictionCallback>,
+}
+
+/// Startup check of the server's `maxmemory-policy`.
+/// Dirty entries have no TTL, so only `allkeys-*` policies can evict them.
+/// - `Off`: no check
+/// - `Warn`: log a warning on `allkeys-*`
+/// - `Refuse`: fail `CacheConnection::try_new_with_config` on `allkeys-*`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionCheck {
+    Off,
+    Warn,
+    Refuse,
+}
+
+/// Called with the entity key of a dirty entry lost to eviction.
+pub type EvictionCallback = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Redacts credentials embedded in `redis_url` and cluster node URLs.
+impl std::fmt::Debug for CacheConnConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut d = f.debug_struct("CacheConnConfig");
+        d.field("redis_url", &redact_url(&self.redis_url))
+            .field("key_prefix", &self.key_prefix)
+            .field("dirty_marker", &self.dirty_marker)
+            .field("delete_marker", &self.delete_marker)
+            .field("lock_marker", &self.lock_marker)
+            .field("missing_marker", &self.missing_marker)
+            .field("ttl_marker", &self.ttl_marker)
+            .field("leader_marker", &self.leader_marker)
+            .field("invalidation_channel", &self.invalidation_channel);
+        #[cfg(feature = "cluster")]
+        d.field("cluster_nodes", &self.cluster_nodes.iter().map(|n| redact_url(n)).collect::<Vec<_>>());
+        #[cfg(feature = "sentinel")]
+        d.field("sentinel", &self.sentinel);
+        d.field("username", &self.username)
+            .field("password", &self.password);
+        #[cfg(feature = "tls")]
+        d.field("tls", &self.tls);
+        d.field("keyspace_events", &self.keyspace_events)
+            .field("eviction_check", &self.eviction_check)
+            .field("on_dirty_evicted", &self.on_dirty_evicted.is_some());
+        d.finish()
+    }
+}
+
+/// Password that never shows up in `Debug` output or logs.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(secret: &str) -> Self {
+        Secret(secret.to_string())
+    }
+
+    /// Raw value, only for handing to the Redis client.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("***")
+    }
+}
+
+/// Hide the userinfo part of a Redis URL (ex: "redis://user:pw@host" => "redis://***@host").
+pub fn redact_url(url: &str) -> String {
+    match (url.find("://"), url.rfind('@')) {
+        (Some(scheme_end), Some(at)) if at > scheme_end => {
+            format!("{}***{}", &url[..scheme_end + 3], &url[at..])
+        }
+        _ => url.to_string(),
+    }
 }
 
 
 impl Default for CacheConnConfig {
     fn default() -> Self {
+        let keys = KeySpace::default();
         CacheConnConfig {
             redis_url: "redis://127.0.0.1/".to_string(),
+            key_prefix: keys.prefix,
+            dirty_marker: keys.dirty,
+            delete_marker: keys.delete,
+            lock_marker: keys.lock,
+            missing_marker: keys.missing,
+            ttl_marker: keys.ttl,
+            leader_marker: keys.leader,
+            invalidation_channel: keys.invalidation,
+            #[cfg(feature = "cluster")]
+            cluster_nodes: Vec::new(),
+            #[cfg(feature = "sentinel")]
+            sentinel: None,
+            username: None,
+            password: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+            keyspace_events: true,
+            eviction_check: EvictionCheck::Warn,
+            on_dirty_evicted: None,
         }
     }
 }
@@ -42,13 +180,246 @@ impl CacheConnConfig {
         self.redis_url = url.to_string();
         self
     }
+
+    /// Set global key prefix (ex: "myapp:prod:").
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.to_string();
+        self
+    }
+
+    /// Set namespace name for dirty entries.
+    pub fn with_dirty_marker(mut self, marker: &str) -> Self {
+        self.dirty_marker = marker.to_string();
+        self
+    }
+
+    /// Set namespace name for delete markers.
+    pub fn with_delete_marker(mut self, marker: &str) -> Self {
+        self.delete_marker = marker.to_string();
+        self
+    }
+
+    /// Set namespace name for single-flight miss locks.
+    pub fn with_lock_marker(mut self, marker: &str) -> Self {
+        self.lock_marker = marker.to_string();
+        self
+    }
+
+    /// Set namespace name for negative cache (404) markers.
+    pub fn with_missing_marker(mut self, marker: &str) -> Self {
+        self.missing_marker = marker.to_string();
+        self
+    }
+
+    /// Set namespace name for the stored TTLs of clean entries.
+    pub fn with_ttl_marker(mut self, marker: &str) -> Self {
+        self.ttl_marker = marker.to_string();
+        self
+    }
+
+    /// Set namespace name for worker leader leases and their tokens.
+    pub fn with_leader_marker(mut self, marker: &str) -> Self {
+        self.leader_marker = marker.to_string();
+        self
+    }
+
+    /// Set the pub/sub channel name of L1 invalidations.
+    pub fn with_invalidation_channel(mut self, channel: &str) -> Self {
+        self.invalidation_channel = channel.to_string();
+        self
+    }
+
+    /// Use Redis Cluster with the given seed node URLs.
+    /// Keys get hash tags so each entity stays in one slot.
+    #[cfg(feature = "cluster")]
+    pub fn with_cluster_nodes(mut self, nodes: &[&str]) -> Self {
+        self.cluster_nodes = nodes.iter().map(|n| n.to_string()).collect();
+        self
+    }
+
+    /// Use Redis Sentinel to discover (and follow) the master.
+    #[cfg(feature = "sentinel")]
+    pub fn with_sentinel(mut self, sentinel: SentinelConfig) -> Self {
+        self.sentinel = Some(sentinel);
+        self
+    }
+
+    /// Set ACL credentials (`username` is `None` for the default user).
+    pub fn with_credentials(mut self, username: Option<&str>, password: &str) -> Self {
+        self.username = username.map(str::to_string);
+        self.password = Some(Secret::new(password));
+        self
+    }
+
+    /// Connect over TLS with custom CA / client certificate.
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Use keyspace notifications to run deletes as soon as they are due.
+    /// Disable on managed Redis that forbids `CONFIG`; deletes are then polled.
+    pub fn with_keyspace_events(mut self, enabled: bool) -> Self {
+        self.keyspace_events = enabled;
+        self
+    }
+
+    /// Set what happens when the server may evict dirty entries.
+    pub fn with_eviction_check(mut self, check: EvictionCheck) -> Self {
+        self.eviction_check = check;
+        self
+    }
+
+    /// Report dirty entries evicted before their flush (lost writes) to `callback`.
+    /// They are also counted in `CacheConn::evicted_dirty_count`.
+    pub fn with_on_dirty_evicted(mut self, callback: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.on_dirty_evicted = Some(Arc::new(callback));
+        self
+    }
+
+    /// `redis_url` without credentials, safe to log.
+    pub fn redacted_url(&self) -> String {
+        redact_url(&self.redis_url)
+    }
+
+    /// Build the redis client (credentials, TLS) for `redis_url`.
+    pub fn build_client(&self) -> redis::RedisResult<Client> {
+        use redis::IntoConnectionInfo;
+
+        let mut info = self.redis_url.as_str().into_connection_info()?;
+        if let Some(username) = &self.username {
+            info.redis.username = Some(username.clone());
+        }
+        if let Some(password) = &self.password {
+            info.redis.password = Some(password.expose().to_string());
+        }
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.tls {
+            return tls.build_client(info);
+        }
+        Client::open(info)
+    }
+
+    /// Whether cluster mode is configured.
+    pub fn is_cluster(&self) -> bool {
+        #[cfg(feature = "cluster")]
+        return !self.cluster_nodes.is_empty();
+        #[cfg(not(feature = "cluster"))]
+        return false;
+    }
+
+    /// Key layout derived from this config.
+    pub fn key_space(&self) -> KeySpace {
+        KeySpace {
+            prefix: self.key_prefix.clone(),
+            dirty: self.dirty_marker.clone(),
+            delete: self.delete_marker.clone(),
+            lock: self.lock_marker.clone(),
+            missing: self.missing_marker.clone(),
+            ttl: self.ttl_marker.clone(),
+            leader: self.leader_marker.clone(),
+            invalidation: self.invalidation_channel.clone(),
+            hash_tags: self.is_cluster(),
+        }
+    }
+}
+
+/// Redis Sentinel config.
+/// - `master_name`: monitored master name (ex: "mymaster")
+/// - `sentinels`: sentinel URLs (ex: "redis://10.0.0.1:26379")
+/// - `username`, `password`: credentials for the Redis master
+/// - `sentinel_username`, `sentinel_password`: credentials for the sentinels
+/// - `db`: logical database on the master
+#[cfg(feature = "sentinel")]
+#[derive(Debug, Clone)]
+pub struct SentinelConfig {
+    pub master_name: String,
+    pub sentinels: Vec<String>,
+    pub username: Option<String>,
+    pub password: Option<Secret>,
+    pub sentinel_username: Option<String>,
+    pub sentinel_password: Option<Secret>,
+    pub db: i64,
+}
+
+#[cfg(feature = "sentinel")]
+impl SentinelConfig {
+    /// Build with master name and sentinel URLs.
+    pub fn new(master_name: &str, sentinels: &[&str]) -> Self {
+        SentinelConfig {
+            master_name: master_name.to_string(),
+            sentinels: sentinels.iter().map(|s| s.to_string()).collect(),
+            username: None,
+            password: None,
+            sentinel_username: None,
+            sentinel_password: None,
+            db: 0,
+        }
+    }
+
+    /// Set credentials for the Redis master.
+    pub fn with_credentials(mut self, username: Option<&str>, password: &str) -> Self {
+        self.username = username.map(str::to_string);
+        self.password = Some(Secret::new(password));
+        self
+    }
+
+    /// Set credentials for the sentinels.
+    pub fn with_sentinel_credentials(mut self, username: Option<&str>, password: &str) -> Self {
+        self.sentinel_username = username.map(str::to_string);
+        self.sentinel_password = Some(Secret::new(password));
+        self
+    }
+
+    /// Set logical database on the master.
+    pub fn with_db(mut self, db: i64) -> Self {
+        self.db = db;
+        self
+    }
+
+    /// Build the redis sentinel client (TLS settings from `conn_config`).
+    fn client(&self, conn_config: &CacheConnConfig) -> redis::RedisResult<redis::sentinel::SentinelClient> {
+        use redis::IntoConnectionInfo;
+        use redis::sentinel::{SentinelClientBuilder, SentinelServerType};
+
+        let addrs = self
+            .sentinels
+            .iter()
+            .map(|url| url.as_str().into_connection_info().map(|info| info.addr))
+            .collect::<redis::RedisResult<Vec<_>>>()?;
+        let mut builder = SentinelClientBuilder::new(addrs, self.master_name.clone(), SentinelServerType::Master)?
+            .set_client_to_redis_db(self.db);
+        if let Some(username) = &self.username {
+            builder = builder.set_client_to_redis_username(username.clone());
+        }
+        if let Some(password) = &self.password {
+            builder = builder.set_client_to_redis_password(password.expose().to_string());
+        }
+        if let Some(username) = &self.sentinel_username {
+            builder = builder.set_client_to_sentinel_username(username.clone());
+        }
+        if let Some(password) = &self.sentinel_password {
+            builder = builder.set_client_to_sentinel_password(password.expose().to_string());
+        }
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &conn_config.tls {
+            crate::tls::install_crypto_provider();
+            builder = builder
+                .set_client_to_redis_tls_mode(redis::TlsMode::Secure)
+                .set_client_to_redis_certificates(tls.certificates());
+        }
+        #[cfg(not(feature = "tls"))]
+        let _ = conn_config;
+        builder.build()
+    }
 }
 
 /// Main cache connection bundle.
 /// Owns: redis client/conn, db pool, config.
 pub struct CacheConnection<DB: Database> {
     pub client: redis::Client,
-    pub conn: MultiplexedConnection,
+    pub conn: CacheConn,
     pub db: Pool<DB>,
     pub config: CacheConnConfig,
 }
@@ -61,22 +432,47 @@ impl<DB: Database> CacheConnection<DB> {
     }
 
     /// Create with custom config.
+    /// Panics where `try_new_with_config` returns an error.
     pub async fn new_with_config(
         db: Pool<DB>,
         config: CacheConnConfig,
     ) -> CacheConnection<DB> {
-        let redis_client = redis::Client::open(config.redis_url.clone()).expect("Invalid Redis URL");
-        let mut con = get_redis_connection_with_retry(&redis_client);
-        let _: () = redis::cmd("CONFIG")
-            .arg("SET")
-            .arg("notify-keyspace-events")
-            .arg("Ex")
-            .query(&mut con)
-            .expect("Failed to set Redis config (PubSub)");
-        let conn = redis_client.get_multiplexed_async_connection().await
-            .expect("Failed to get Redis multiplexed connection");
+        CacheConnection::try_new_with_config(db, config).await
+            .unwrap_or_else(|e| panic!("Failed to start the Redis cache: {e}"))
+    }
 
-        CacheConnection { client: redis_client, conn, db, config }
+    /// Create with custom config. Returns an error if the Redis config is
+    /// invalid, the connection still fails after retries, or `EvictionCheck::Refuse` finds a
+    /// `maxmemory-policy` that may evict dirty entries.
+    pub async fn try_new_with_config(
+        db: Pool<DB>,
+        config: CacheConnConfig,
+    ) -> redis::RedisResult<CacheConnection<DB>> {
+        #[cfg(feature = "cluster")]
+        if config.is_cluster() {
+            let (client, mut conn) = connect_cluster(&config).await?;
+            check_eviction_policy(&mut conn, config.eviction_check).await?;
+            return Ok(CacheConnection { client, conn, db, config });
+        }
+
+        #[cfg(feature = "sentinel")]
+        if let Some(sentinel) = &config.sentinel {
+            let sentinel_client = sentinel.client(&config)?;
+            let mut conn = CacheConn::sentinel(sentinel_client, config.keyspace_events).await?;
+            let client = conn.current_client().expect("Sentinel connection has a master");
+            check_eviction_policy(&mut conn, config.eviction_check).await?;
+            return Ok(CacheConnection { client, conn, db, config });
+        }
+
+        let redis_client = config.build_client()?;
+        let mut conn = get_redis_connection_with_retry(&redis_client).await?;
+        if config.keyspace_events {
+            crate::connection::try_enable_expire_events(&mut conn).await;
+        }
+
+        let mut conn = CacheConn::single(redis_client.clone(), conn, config.keyspace_events);
+        check_eviction_policy(&mut conn, config.eviction_check).await?;
+        Ok(CacheConnection { client: redis_client, conn, db, config })
     }
 
     /// Build cache manager + spawn background workers.
@@ -90,29 +486,92 @@ impl<DB: Database> CacheConnection<DB> {
         put_function: F,
         delete_function: G,
         put_cache_function: fn(String, String) -> String,
-    ) -> CacheManager
+    ) -> CacheManager<DB>
     where
         F: Fn(Pool<DB>, String) -> Fut1 + Send + Sync + 'static,
         G: Fn(Pool<DB>, String) -> Fut2 + Send + Sync + 'static,
-        Fut1: Future<Output = ()> + Send + 'static,
-        Fut2: Future<Output = ()> + Send + 'static,
+        Fut1: Future + Send + 'static,
+        Fut1::Output: CallbackOutcome,
+        Fut2: Future + Send + 'static,
+        Fut2::Output: CallbackOutcome,
     {
         CacheManager::new(self.db.clone(),
                         self.client.clone(),
                         self.conn.clone(),
+                        self.config.key_space(),
+                        self.config.on_dirty_evicted.clone(),
                         key,
                         put_function,
                         delete_function,
                         put_cache_function)
     }
+
+    /// Build a registry serving many resources with one middleware
+    /// and one set of background workers.
+    pub fn get_registry(&self) -> CacheRegistryBuilder<DB> {
+        CacheRegistryBuilder::new(self.db.clone(),
+                                  self.client.clone(),
+                                  self.conn.clone(),
+                                  self.config.key_space(),
+                                  self.config.on_dirty_evicted.clone())
+    }
 }
 
 
+/// How dirty entries reach the DB.
+/// - `Poll`: the leader scans `dirty:` keys every `write_duration` seconds
+/// - `Stream`: every PUT appends an event to the resource's write stream,
+///   applied by `workers` concurrent workers per replica (consumer group)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteBehind {
+    Poll,
+    Stream { workers: usize },
+}
+
+/// When a PUT cache hit reaches the DB.
+/// - `WriteBehind`: stored dirty, written later by the background workers
+/// - `WriteThrough`: written by `put_function` before responding; the cache
+///   is only updated once the DB write succeeded, a DB error is a 500
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteMode {
+    WriteBehind,
+    WriteThrough,
+}
+
+/// Coalescing of concurrent GET misses on one key.
+/// - `Off`: every miss runs the handler
+/// - `Local { wait }`: one handler run per key and process, the other
+///   requests wait up to `wait` and are served the cached result
+/// - `Distributed { wait }`: one handler run per key across replicas,
+///   through a Redis lock (`lock:{key}`) that expires after `wait`
+///
+/// A request that waited in vain runs the handler itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SingleFlight {
+    Off,
+    Local { wait: Duration },
+    Distributed { wait: Duration },
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct CacheConfig {
     pub write_duration: u64,
     pub ttl_clean: u64,
     pub ttl_deleted: u64,
+    pub ttl_not_found: u64,
+    pub write_mode: WriteMode,
+    pub write_behind: WriteBehind,
+    pub refresh_ahead: Option<f64>,
+    pub single_flight: SingleFlight,
+    pub stale_while_revalidate: u64,
+    pub stale_if_error: u64,
+    pub handler_timeout: Option<Duration>,
+    pub ttl_jitter: u8,
+    pub l1_capacity: usize,
+    pub l1_ttl: Duration,
+    pub max_body_size: Option<usize>,
+    pub max_request_size: Option<usize>,
+    pub reclaim_idle: Duration,
 }
 
 
@@ -123,6 +582,20 @@ impl CacheConfig {
             write_duration: 5, // Default to 5 seconds
             ttl_clean: 60,     // Default to 60 seconds
             ttl_deleted: 10,   // Default to 10 seconds
+            ttl_not_found: 0,  // Negative caching off
+            write_mode: WriteMode::WriteBehind,
+            write_behind: WriteBehind::Poll,
+            refresh_ahead: None,
+            single_flight: SingleFlight::Off,
+            stale_while_revalidate: 0,
+            stale_if_error: 0,
+            handler_timeout: None,
+            ttl_jitter: 0,
+            l1_capacity: 0, // In-process cache off
+            l1_ttl: Duration::from_secs(1),
+            max_body_size: None,    // Cache responses of any size
+            max_request_size: None, // Merge PUT bodies of any size
+            reclaim_idle: Duration::from_secs(30),
         }
     }
     /// Set custom write-behind interval.
@@ -131,7 +604,7 @@ impl CacheConfig {
         self
     }
 
-    /// Set custom TTL for clean entries.
+    /// Set custom TTL for clean entries (0 = don't cache).
     pub fn with_clean_ttl(mut self, ttl: u64) -> Self {
         self.ttl_clean = ttl;
         self
@@ -142,6 +615,128 @@ impl CacheConfig {
         self.ttl_deleted = ttl;
         self
     }
+
+    /// Remember GET 404s from the handler for `ttl` seconds (0 = off).
+    /// A PUT or create of the same id forgets them.
+    pub fn with_not_found_ttl(mut self, ttl: u64) -> Self {
+        self.ttl_not_found = ttl;
+        self
+    }
+
+    /// Set when PUT cache hits are written to DB.
+    pub fn with_write_mode(mut self, mode: WriteMode) -> Self {
+        self.write_mode = mode;
+        self
+    }
+
+    /// Set how dirty entries are written to DB.
+    pub fn with_write_behind(mut self, mode: WriteBehind) -> Self {
+        self.write_behind = mode;
+        self
+    }
+
+    /// Refresh clean entries in the background when a GET hit finds less
+    /// than `fraction` (0.0-1.0) of `ttl_clean` left.
+    pub fn with_refresh_ahead(mut self, fraction: f64) -> Self {
+        if !(0.0..=1.0).contains(&fraction) {
+            panic!("refresh-ahead fraction must be within 0.0..=1.0, got {fraction}");
+        }
+        self.refresh_ahead = Some(fraction);
+        self
+    }
+
+    /// Set how concurrent GET misses on one key are coalesced.
+    pub fn with_single_flight(mut self, mode: SingleFlight) -> Self {
+        self.single_flight = mode;
+        self
+    }
+
+    /// Serve clean entries up to `grace` seconds past `ttl_clean` as stale
+    /// while a background revalidation runs.
+    pub fn with_stale_while_revalidate(mut self, grace: u64) -> Self {
+        self.stale_while_revalidate = grace;
+        self
+    }
+
+    /// Serve clean entries up to `grace` seconds past `ttl_clean` as stale
+    /// when revalidating through the handler fails (5xx or timeout).
+    pub fn with_stale_if_error(mut self, grace: u64) -> Self {
+        self.stale_if_error = grace;
+        self
+    }
+
+    /// Give up on the handler after `timeout` when a stale copy can be served instead.
+    pub fn with_handler_timeout(mut self, timeout: Duration) -> Self {
+        self.handler_timeout = Some(timeout);
+        self
+    }
+
+    /// Shorten every TTL the cache sets by a random 0-`percent`%, so entries
+    /// written together (warm-up, deploy) do not expire together.
+    pub fn with_ttl_jitter(mut self, percent: u8) -> Self {
+        if percent > 100 {
+            panic!("TTL jitter must be within 0..=100 percent, got {percent}");
+        }
+        self.ttl_jitter = percent;
+        self
+    }
+
+    /// Keep up to `capacity` fresh clean entries in process for up to `ttl`,
+    /// so repeated GETs skip Redis. Writes drop them on every replica
+    /// through pub/sub; dirty entries are always read from Redis.
+    /// The invalidation listener only runs for configs with L1, applied
+    /// through `CacheManager::with_config` or before the registry starts.
+    pub fn with_l1(mut self, capacity: usize, ttl: Duration) -> Self {
+        self.l1_capacity = capacity;
+        self.l1_ttl = ttl;
+        self
+    }
+
+    /// Stream handler responses over `bytes` through without caching them.
+    pub fn with_max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_size = Some(bytes);
+        self
+    }
+
+    /// Reject PUT bodies over `bytes` with 413 instead of merging them.
+    pub fn with_max_request_size(mut self, bytes: usize) -> Self {
+        self.max_request_size = Some(bytes);
+        self
+    }
+
+    /// Retry stream entries (deletes, stream write-behind events) left
+    /// unacknowledged for `idle` (failed callback, crashed replica).
+    /// Checked on startup, then every `idle`: entries of a replica that
+    /// crashed just before a restart wait up to `idle` after it.
+    pub fn with_reclaim_idle(mut self, idle: Duration) -> Self {
+        self.reclaim_idle = idle;
+        self
+    }
+
+    /// `ttl` seconds (at most `MAX_TTL`) minus the random jitter, at least 1 second.
+    pub(crate) fn jittered(&self, ttl: u64) -> u64 {
+        let ttl = ttl.min(MAX_TTL);
+        let spread = ttl * self.ttl_jitter as u64 / 100;
+        if spread == 0 {
+            return ttl;
+        }
+        (ttl - crate::ttl::random_below(spread + 1)).max(1)
+    }
+
+    /// Seconds a clean entry stays in Redis past its fresh TTL: the longest stale grace.
+    pub(crate) fn stale_grace(&self) -> u64 {
+        self.stale_while_revalidate.max(self.stale_if_error).min(MAX_TTL)
+    }
+
+    /// TTLs of a clean entry fresh for `ttl` seconds: jittered, plus the
+    /// stale grace. `None` for 0, which means "don't cache".
+    pub(crate) fn clean_ttls(&self, ttl: u64) -> Option<CleanTtl> {
+        if ttl == 0 {
+            return None;
+        }
+        let fresh = self.jittered(ttl);
+        Some(CleanTtl { fresh, expiry: fresh + self.stale_grace() })
+    }
 }
 
 impl Default for CacheConfig {
@@ -152,74 +747,141 @@ impl Default for CacheConfig {
 
 /// Central cache manager struct.
 /// Background workers start on creation.
-pub struct CacheManager {
-    pub conn: MultiplexedConnection,
+pub struct CacheManager<DB: Database> {
+    pub conn: CacheConn,
+    pub keys: KeySpace,
     pub key: String,
     pub config: Arc<Mutex<CacheConfig>>,
 
     /* Handler for Cache Write-behind */
     put_cache_function: fn(String, String) -> String,
-    write_behind_handle: Option<JoinHandle<()>>,
-    delete_event_handle: Option<JoinHandle<()>>,
-
-    /* For graceful Shutdown */
-    cancellation_token: CancellationToken,
-    is_shutdown: std::sync::atomic::AtomicBool,
+    write_function: WriteCallback,
+    loader: Option<LoadCallback>,
+    refreshing: InFlight,
+    loading: InFlight,
+    ttl_policy: SharedTtlPolicy,
+    l1: L1,
+    // Pool of the callbacks, for `with_loader`
+    db: Pool<DB>,
+    workers: Workers,
 }
 
-impl CacheManager {
+impl<DB: Database> CacheManager<DB> {
     /// Construct new manager, spawns background workers.
     #[allow(clippy::too_many_arguments)]
-    fn new<F, G, Fut1, Fut2, DB: Database>(
+    fn new<F, G, Fut1, Fut2>(
         /* Datebase */
         db: Pool<DB>,
 
         /* redis setting */
         client: redis::Client,
-        conn: MultiplexedConnection,
+        conn: CacheConn,
+        keys: KeySpace,
+        on_dirty_evicted: Option<EvictionCallback>,
         key: String,
 
         /* user-defined function */
         put_function: F,
         delete_function: G,
         put_cache_function: fn(String, String) -> String,
-    ) -> CacheManager
+    ) -> CacheManager<DB>
     where
         F: Fn(Pool<DB>, String) -> Fut1 + Send + Sync + 'static,
         G: Fn(Pool<DB>, String) -> Fut2 + Send + Sync + 'static,
-        Fut1: Future<Output = ()> + Send + 'static,
-        Fut2: Future<Output = ()> + Send + 'static,
+        Fut1: Future + Send + 'static,
+        Fut1::Output: CallbackOutcome,
+        Fut2: Future + Send + 'static,
+        Fut2::Output: CallbackOutcome,
     {
-        let cancellation_token = CancellationToken::new();
         let config = Arc::new(Mutex::new(CacheConfig::default()));
+        let ttl_policy = SharedTtlPolicy::default();
+        let l1 = L1::default();
+        let resource = SyncResource {
+            root_key: key.clone(),
+            config: Arc::clone(&config),
+            ttl_policy: ttl_policy.clone(),
+            l1: l1.clone(),
+            put_function: cache_sync::boxed_callback(put_function),
+            delete_function: cache_sync::boxed_callback(delete_function),
+        };
 
+        let write_function = cache_sync::bind_pool(db.clone(), Arc::clone(&resource.put_function));
         // Write-behind + delete event listeners
-        let write_behind_handle = tokio::spawn(cache_sync::write_behind(conn.clone(), db.clone(), key.clone(), Arc::clone(&config), put_function, cancellation_token.clone()));
-        let delete_event_handle = tokio::spawn(cache_sync::delete_event_listener(client, db.clone(), key.clone(), delete_function, cancellation_token.clone()));
-        
+        let workers = Workers::spawn(db.clone(), client, conn.clone(), keys.clone(), vec![resource], on_dirty_evicted);
+
         CacheManager {
             conn,
+            keys,
             key,
             config,
             put_cache_function,
-            write_behind_handle: Some(write_behind_handle),
-            delete_event_handle: Some(delete_event_handle),
-            cancellation_token,
-            is_shutdown: AtomicBool::new(false),
+            write_function,
+            loader: None,
+            refreshing: InFlight::default(),
+            loading: InFlight::default(),
+            ttl_policy,
+            l1,
+            db,
+            workers,
         }
     }
 
     /// Set a new cache configuration.
-    pub fn with_config(self, config: CacheConfig) -> Self {
+    /// Starts the L1 invalidation listener if `config` enables L1.
+    pub fn with_config(mut self, config: CacheConfig) -> Self {
         *self.config.lock().unwrap() = config;
+        if config.l1_capacity > 0 {
+            self.workers.spawn_l1_listener(vec![(self.key.clone(), self.l1.clone())]);
+        }
         self
     }
 
+    /// Fill cache misses from `loader` (`Pool<DB>`, id) instead of the handler.
+    ///
+    /// Called on GET and PUT misses with the entity id (ex: "1" for "posts:1");
+    /// `Some(body)` is cached as clean, `None` falls through to the handler.
+    /// Also used by `get_or_load`, so jobs outside HTTP can warm the cache.
+    pub fn with_loader<L, Fut>(mut self, loader: L) -> Self
+    where
+        L: Fn(Pool<DB>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Option<String>> + Send + 'static,
+    {
+        self.loader = Some(cache_sync::bind_loader(self.db.clone(), cache_sync::boxed_loader(loader)));
+        self
+    }
+
+    /// Pick TTLs per entry (route, entity, handler response) with `policy`.
+    /// Replaces the default `ResponseTtl`, also for the background workers.
+    pub fn with_ttl_policy(self, policy: impl TtlPolicy + 'static) -> Self {
+        self.ttl_policy.set(Arc::new(policy));
+        self
+    }
+
+    /// Cached body of entity `id`, loaded through the loader on a miss.
+    /// `None` if deleted, not found or on Redis errors.
+    pub async fn get_or_load(&self, id: &str) -> Option<String> {
+        self.get_state().get_or_load(id).await
+    }
+
+    /// Drop the 404 marker of entity `id`. See `CacheState::forget_missing`.
+    pub async fn forget_missing(&self, id: &str) -> redis::RedisResult<()> {
+        self.get_state().forget_missing(id).await
+    }
+
     /// Return CacheState for Axum middleware injection.
+    /// The middleware only caches paths under this manager's `key`.
     pub fn get_state(&self) -> CacheState {
         CacheState {
             conn: self.conn.clone(),
+            keys: self.keys.clone(),
+            root_key: self.key.clone(),
             write_to_cache: self.put_cache_function,
+            write_function: Arc::clone(&self.write_function),
+            loader: self.loader.clone(),
+            refreshing: self.refreshing.clone(),
+            loading: self.loading.clone(),
+            ttl_policy: self.ttl_policy.clone(),
+            l1: self.l1.clone(),
             config: self.config.clone(),
         }
     }
@@ -227,6 +889,102 @@ impl CacheManager {
     /// Signals shutdown and waits for background tasks to complete.
     pub async fn shutdown(&mut self) {
         println!("{} Cache manager graceful shutdown", "Shutdown".red().bold());
+        self.workers.shutdown().await;
+        println!("{} Cache manager shutdown gracefully.", "Done".green().bold());
+    }
+
+}
+
+impl<DB: Database> Drop for CacheManager<DB> {
+    fn drop(&mut self) {
+        if !self.workers.is_shutdown() {
+            // 개발 중이라면 panic도 가능
+            eprintln!("⚠️ CacheManager dropped without shutdown(). This may cause data loss.");
+            #[cfg(debug_assertions)]
+            panic!("CacheManager dropped without calling shutdown()");
+        }
+    }
+}
+
+/// Background workers shared by `CacheManager` and `CacheRegistry`:
+/// one write-behind scheduler, one write stream consumer, one pending
+/// delete poller, one L1 invalidation listener and, with
+/// keyspace notifications, one expire and one eviction listener. Across replicas, each
+/// resource is flushed and polled only by its leader lease holder.
+pub(crate) struct Workers {
+    write_behind_handle: Option<JoinHandle<()>>,
+    write_stream_handle: Option<JoinHandle<()>>,
+    delete_poll_handle: Option<JoinHandle<()>>,
+    eviction_handle: Option<JoinHandle<()>>,
+    l1_handle: Option<JoinHandle<()>>,
+    leases: Arc<Leases>,
+    client: redis::Client,
+    conn: CacheConn,
+    keys: KeySpace,
+
+    /* For graceful Shutdown */
+    cancellation_token: CancellationToken,
+    is_shutdown: AtomicBool,
+}
+
+impl Workers {
+    /// Spawn the workers for the given resources.
+    pub(crate) fn spawn<DB: Database>(
+        db: Pool<DB>,
+        client: redis::Client,
+        conn: CacheConn,
+        keys: KeySpace,
+        resources: Vec<SyncResource<DB>>,
+        on_dirty_evicted: Option<EvictionCallback>,
+    ) -> Workers {
+        let cancellation_token = CancellationToken::new();
+        let leases = Arc::new(Leases::new(&keys, resources.iter().map(|r| r.root_key.as_str())));
+        let write_behind_handle = tokio::spawn(cache_sync::write_behind(conn.clone(), db.clone(), keys.clone(), resources.clone(), Arc::clone(&leases), cancellation_token.clone()));
+        let write_stream_handle = tokio::spawn(cache_sync::write_streams(conn.clone(), db.clone(), keys.clone(), resources.clone(), cancellation_token.clone()));
+        let l1s: Vec<(String, L1)> = resources
+            .iter()
+            .filter(|r| r.config.lock().unwrap().l1_capacity > 0)
+            .map(|r| (r.root_key.clone(), r.l1.clone()))
+            .collect();
+        let eviction_handle = conn.expire_events().then(|| {
+            tokio::spawn(cache_sync::eviction_listener(client.clone(), conn.clone(), keys.clone(), resources.clone(), on_dirty_evicted, cancellation_token.clone()))
+        });
+        let delete_poll_handle = tokio::spawn(cache_sync::delete_poller(conn.clone(), db, keys.clone(), resources, Arc::clone(&leases), cancellation_token.clone()));
+
+        let mut workers = Workers {
+            write_behind_handle: Some(write_behind_handle),
+            write_stream_handle: Some(write_stream_handle),
+            delete_poll_handle: Some(delete_poll_handle),
+            eviction_handle,
+            l1_handle: None,
+            leases,
+            client,
+            conn,
+            keys,
+            cancellation_token,
+            is_shutdown: AtomicBool::new(false),
+        };
+        workers.spawn_l1_listener(l1s);
+        workers
+    }
+
+    /// Start the L1 invalidation listener for `l1s` (root, L1), unless
+    /// there is none or it already runs.
+    pub(crate) fn spawn_l1_listener(&mut self, l1s: Vec<(String, L1)>) {
+        if l1s.is_empty() || self.l1_handle.is_some() {
+            return;
+        }
+        self.l1_handle = Some(tokio::spawn(l1::invalidation_listener(
+            self.client.clone(),
+            self.conn.clone(),
+            self.keys.clone(),
+            l1s,
+            self.cancellation_token.clone(),
+        )));
+    }
+
+    /// Signals shutdown and waits for background tasks to complete.
+    pub(crate) async fn shutdown(&mut self) {
         // Signal shutdown to background tasks
         self.cancellation_token.cancel();
         // Wait for tasks to finish
@@ -234,65 +992,182 @@ impl CacheManager {
 
         /* can't move when drop trait is impl */
         //let _ = tokio::join!(self.write_behind_handle, self.delete_event_handle);
-        if let Some(handle) = self.write_behind_handle.take() {
+        // Stream events first, the final poll flush catches anything left
+        if let Some(handle) = self.write_stream_handle.take() {
             let _ = handle.await;
         }
 
-        if let Some(handle) = self.delete_event_handle.take() {
+        if let Some(handle) = self.write_behind_handle.take() {
             let _ = handle.await;
         }
 
-        println!("{} Cache manager shutdown gracefully.", "Done".green().bold());
-    }
+        if let Some(handle) = self.eviction_handle.take() {
+            let _ = handle.await;
+        }
 
-}
+        if let Some(handle) = self.l1_handle.take() {
+            let _ = handle.await;
+        }
 
-impl Drop for CacheManager {
-    fn drop(&mut self) {
-        if !self.is_shutdown.load(Ordering::SeqCst) {
-            // 개발 중이라면 panic도 가능
-            eprintln!("⚠️ CacheManager dropped without shutdown(). This may cause data loss.");
-            #[cfg(debug_assertions)]
-            panic!("CacheManager dropped without calling shutdown()");
+        // Last: runs every remaining pending delete
+        if let Some(handle) = self.delete_poll_handle.take() {
+            let _ = handle.await;
         }
+
+        // Hand leadership over to another replica right away
+        self.leases.release_all(&mut self.conn).await;
+    }
+
+    pub(crate) fn is_shutdown(&self) -> bool {
+        self.is_shutdown.load(Ordering::SeqCst)
     }
 }
 
 /// Minimal state for `middleware`.
-/// - `conn`: multiplexed redis connection
+/// - `conn`: redis connection (single or cluster)
+/// - `keys`: key layout (prefix, dirty/delete markers)
+/// - `root_key`: resource the manager flushes (ex: "posts")
 /// - `write_to_cache`: custom JSON merge function for PUT
+/// - `write_function`: `put_function` bound to the DB pool (write-through)
+/// - `loader`: optional read-through loader bound to the DB pool
+/// - `refreshing`: keys with a refresh-ahead running
+/// - `loading`: keys with a single-flight miss running
+/// - `ttl_policy`: per-entry TTLs (`ResponseTtl` by default)
+/// - `l1`: in-process copy of fresh clean entries (`CacheConfig::with_l1`)
 #[derive(Clone)]
 pub struct CacheState {
-    pub conn: MultiplexedConnection,
+    pub conn: CacheConn,
+    pub keys: KeySpace,
+    pub root_key: String,
     pub write_to_cache: fn(String, String) -> String,
+    pub(crate) write_function: WriteCallback,
+    pub(crate) loader: Option<LoadCallback>,
+    pub(crate) refreshing: InFlight,
+    pub(crate) loading: InFlight,
+    pub(crate) ttl_policy: SharedTtlPolicy,
+    pub(crate) l1: L1,
     pub config: Arc<Mutex<CacheConfig>>,
 }
 
-fn get_redis_connection_with_retry(redis_client: &Client) -> Connection {
+impl CacheState {
+    /// Fresh TTL of `key` from the policy, given the handler response if any.
+    pub(crate) fn clean_ttl(&self, config: &CacheConfig, key: &str, response: Option<&Parts>) -> u64 {
+        let ctx = TtlContext {
+            root: &self.root_key,
+            key,
+            headers: response.map(|parts| &parts.headers),
+            extensions: response.map(|parts| &parts.extensions),
+        };
+        self.ttl_policy.clean(config, &ctx)
+    }
+
+    /// Cached body of entity `id` under `root_key`, loaded through the
+    /// loader on a miss. `None` if deleted, not found or on Redis errors.
+    pub async fn get_or_load(&self, id: &str) -> Option<String> {
+        let key = format!("{}:{}", self.root_key, id);
+        let mut conn = self.conn.clone();
+        match middleware::read_through(self, &mut conn, &key).await {
+            Ok(Snapshot { entry: Entry::Dirty(body) | Entry::Clean(body), .. }) => Some(body),
+            Ok(_) => None,
+            Err(e) => {
+                eprintln!("❌ Failed to read {key}: {e}");
+                None
+            }
+        }
+    }
+
+    /// Drop the 404 marker of entity `id`, for creates the middleware
+    /// cannot see (ex: no `Location` header, or written outside HTTP).
+    pub async fn forget_missing(&self, id: &str) -> redis::RedisResult<()> {
+        let key = format!("{}:{}", self.root_key, id);
+        entity::forget_missing(&mut self.conn.clone(), &self.keys, &key).await
+    }
+}
+
+/// Warn about, or refuse, a `maxmemory-policy` that may evict dirty entries.
+/// A server that forbids `CONFIG` (managed Redis) is only warned about.
+async fn check_eviction_policy(conn: &mut CacheConn, check: EvictionCheck) -> redis::RedisResult<()> {
+    if check == EvictionCheck::Off {
+        return Ok(());
+    }
+    let policy = redis::cmd("CONFIG")
+        .arg("GET")
+        .arg("maxmemory-policy")
+        .query_async::<std::collections::HashMap<String, String>>(conn)
+        .await
+        .map(|config| config.get("maxmemory-policy").cloned().unwrap_or_default());
+    let policy = match policy {
+        Ok(policy) => policy,
+        Err(e) => {
+            eprintln!("⚠️ Could not read maxmemory-policy, make sure it is not allkeys-*: {e}");
+            return Ok(());
+        }
+    };
+    if !policy.starts_with("allkeys-") {
+        return Ok(());
+    }
+    let message = format!("Redis maxmemory-policy {policy} may evict dirty entries before they are flushed; use noeviction or a volatile-* policy");
+    if check == EvictionCheck::Refuse {
+        return Err(redis::RedisError::from((redis::ErrorKind::ClientError, "Unsafe maxmemory-policy", message)));
+    }
+    eprintln!("⚠️ {message}");
+    Ok(())
+}
+
+/// Connect with retries, then return the last error. Logs only the server
+/// address, never credentials.
+async fn get_redis_connection_with_retry(redis_client: &Client) -> redis::RedisResult<MultiplexedConnection> {
     let mut attempts = 0;
     let max_attempts = 6;
     let wait_duration = Duration::from_secs(10);
+    let addr = &redis_client.get_connection_info().addr;
 
     loop {
-        match redis_client.get_connection() {
-            Ok(conn) => return conn,
+        match redis_client.get_multiplexed_async_connection().await {
+            Ok(conn) => return Ok(conn),
             Err(err) => {
                 attempts += 1;
                 if attempts >= max_attempts {
-                    panic!("Failed to connect to Redis after {} attempts: {}", attempts, err);
-                } else {
-                    eprintln!(
-                        "Redis connection failed (attempt {}/{}), retrying in {}s...: {}",
-                        attempts,
-                        max_attempts,
-                        wait_duration.as_secs(),
-                        err
-                    );
-                    sleep(wait_duration);
+                    eprintln!("❌ Failed to connect to Redis {} after {} attempts: {}", addr, attempts, err);
+                    return Err(err);
                 }
+                eprintln!(
+                    "Redis connection to {} failed (attempt {}/{}), retrying in {}s...: {}",
+                    addr,
+                    attempts,
+                    max_attempts,
+                    wait_duration.as_secs(),
+                    err
+                );
+                tokio::time::sleep(wait_duration).await;
             }
         }
     }
 }
 
-
+/// Connect to Redis Cluster and enable expire notifications on every node.
+#[cfg(feature = "cluster")]
+async fn connect_cluster(config: &CacheConnConfig) -> redis::RedisResult<(Client, CacheConn)> {
+    let nodes = &config.cluster_nodes;
+    let mut builder = redis::cluster::ClusterClient::builder(nodes.to_vec());
+    if let Some(username) = &config.username {
+        builder = builder.username(username.clone());
+    }
+    if let Some(password) = &config.password {
+        builder = builder.password(password.expose().to_string());
+    }
+    #[cfg(feature = "tls")]
+    if let Some(tls) = &config.tls {
+        crate::tls::install_crypto_provider();
+        builder = builder.tls(redis::TlsMode::Secure).certs(tls.certificates());
+    }
+    let cluster_client = builder.build()?;
+    let mut conn = cluster_client.get_async_connection().await?;
+    // CONFIG SET is routed to all nodes
+    if config.keyspace_events {
+        crate::connection::try_enable_expire_events(&mut conn).await;
+    }
+    let seed = CacheConnConfig { redis_url: nodes[0].clone(), ..config.clone() }
+        .build_client()?;
+    Ok((seed, CacheConn::cluster(conn, config.keyspace_events)))
+}
@@ -1,124 +1,175 @@
 // src/cache_sync.rs
 
-use redis::{aio::MultiplexedConnection,
-            AsyncCommands,
+use redis::{AsyncCommands,
             Script};
+use redis::streams::StreamId;
 use tokio_util::sync::CancellationToken;
 use sqlx::{Database, Pool};
-use tokio::time::{Duration};
+use tokio::time::{Duration, Instant};
 use colored::*;
 use futures_util::StreamExt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::cache::{CacheConfig, EvictionCallback, WriteBehind};
+use crate::connection::{self, CacheConn};
+use crate::entity;
+use crate::keys::KeySpace;
+use crate::lease::{self, Leases};
+use crate::streams::StreamConsumer;
+use crate::l1::{self, L1};
+use crate::ttl::{SharedTtlPolicy, TtlContext};
 
-/// Write-behind background worker.
-/// Every N seconds, scans dirty:* keys and writes to DB, then cleans up.
-pub async fn write_behind<F, Fut, DB>(
-    mut conn: MultiplexedConnection,
-    db: Pool<DB>,
-    root_key: String,
-    config: std::sync::Arc<std::sync::Mutex<crate::cache::CacheConfig>>,
-    write_function: F,
-    token: CancellationToken,
-)
+/// Outcome of a user DB callback.
+/// `()` always succeeds; an `Err` is logged and the work is retried later.
+pub trait CallbackOutcome {
+    fn into_result(self) -> Result<(), String>;
+}
+
+impl CallbackOutcome for () {
+    fn into_result(self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl<E: std::fmt::Display> CallbackOutcome for Result<(), E> {
+    fn into_result(self) -> Result<(), String> {
+        self.map_err(|e| e.to_string())
+    }
+}
+
+/// Boxed future returned by type-erased DB callbacks.
+pub(crate) type BoxFuture = Pin<Box<dyn Future<Output = Result<(), String>> + Send>>;
+
+/// Type-erased user DB callback (`put_function` / `delete_function`).
+pub(crate) type DbCallback<DB> = Arc<dyn Fn(Pool<DB>, String) -> BoxFuture + Send + Sync>;
+
+/// User DB write callback bound to its pool, for synchronous writes
+/// from the middleware (`WriteMode::WriteThrough`).
+pub(crate) type WriteCallback = Arc<dyn Fn(String) -> BoxFuture + Send + Sync>;
+
+/// Bind `callback` to `db`, so `CacheState` stays free of the DB type.
+pub(crate) fn bind_pool<DB: Database>(db: Pool<DB>, callback: DbCallback<DB>) -> WriteCallback {
+    Arc::new(move |s| callback(db.clone(), s))
+}
+
+/// Boxed future returned by type-erased loaders: the entity body, if found.
+pub(crate) type LoadFuture = Pin<Box<dyn Future<Output = Option<String>> + Send>>;
+
+/// Type-erased user read-through loader (`Pool<DB>`, entity id).
+pub(crate) type DbLoader<DB> = Arc<dyn Fn(Pool<DB>, String) -> LoadFuture + Send + Sync>;
+
+/// Loader bound to its pool, called by the middleware and `get_or_load`.
+pub(crate) type LoadCallback = Arc<dyn Fn(String) -> LoadFuture + Send + Sync>;
+
+/// Erase a user loader.
+pub(crate) fn boxed_loader<DB, L, Fut>(loader: L) -> DbLoader<DB>
 where
-    F: Fn(Pool<DB>, String) -> Fut,
-    Fut: std::future::Future<Output = ()>,
     DB: Database,
+    L: Fn(Pool<DB>, String) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Option<String>> + Send + 'static,
 {
+    Arc::new(move |db, id| Box::pin(loader(db, id)))
+}
+
+/// Bind `loader` to `db`, like `bind_pool`.
+pub(crate) fn bind_loader<DB: Database>(db: Pool<DB>, loader: DbLoader<DB>) -> LoadCallback {
+    Arc::new(move |id| loader(db.clone(), id))
+}
+
+/// Erase a user callback so resources with different closures share one worker.
+pub(crate) fn boxed_callback<DB, F, Fut>(function: F) -> DbCallback<DB>
+where
+    DB: Database,
+    F: Fn(Pool<DB>, String) -> Fut + Send + Sync + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: CallbackOutcome,
+{
+    let function = Arc::new(function);
+    Arc::new(move |db, s| {
+        let future = function(db, s);
+        Box::pin(async move { future.await.into_result() })
+    })
+}
+
+/// One cached resource as seen by the background workers.
+pub(crate) struct SyncResource<DB: Database> {
+    pub root_key: String,
+    pub config: Arc<Mutex<CacheConfig>>,
+    pub ttl_policy: SharedTtlPolicy,
+    pub l1: L1,
+    pub put_function: DbCallback<DB>,
+    pub delete_function: DbCallback<DB>,
+}
+
+impl<DB: Database> Clone for SyncResource<DB> {
+    fn clone(&self) -> Self {
+        SyncResource {
+            root_key: self.root_key.clone(),
+            config: Arc::clone(&self.config),
+            ttl_policy: self.ttl_policy.clone(),
+            l1: self.l1.clone(),
+            put_function: Arc::clone(&self.put_function),
+            delete_function: Arc::clone(&self.delete_function),
+        }
+    }
+}
+
+/// Write-behind background worker.
+/// Shared scheduler: each resource is flushed every `write_duration` seconds.
+/// A flush scans dirty:* keys, writes them to DB, then cleans up.
+/// Only the replica holding a resource's leader lease flushes it.
+/// Resources in `WriteBehind::Stream` mode are left to `write_streams`;
+/// they are only swept every `reclaim_idle`, as a safety net for entries
+/// whose write event was lost, and flushed on shutdown.
+pub(crate) async fn write_behind<DB: Database>(
+    mut conn: CacheConn,
+    db: Pool<DB>,
+    keys: KeySpace,
+    resources: Vec<SyncResource<DB>>,
+    leases: Arc<Leases>,
+    token: CancellationToken,
+) {
     println!("{} Redis write behind thread", "Start".green().bold());
+    let mut last_flush = vec![Instant::now(); resources.len()];
     loop {
-        let duration = config.lock().unwrap().write_duration;
+        // Next due flush across all resources (config may change at runtime)
+        let due: Vec<Instant> = resources
+            .iter()
+            .zip(&last_flush)
+            .map(|(resource, last)| {
+                let config = *resource.config.lock().unwrap();
+                match config.write_behind {
+                    WriteBehind::Poll => *last + Duration::from_secs(config.write_duration),
+                    WriteBehind::Stream { .. } => *last + config.reclaim_idle,
+                }
+            })
+            .collect();
+        let Some(next_due) = due.iter().min().copied() else {
+            token.cancelled().await;
+            break;
+        };
+
         tokio::select! {
-            _ = tokio::time::sleep(Duration::from_secs(duration)) => {
-                // Scan for dirty keys
-                let dirty_key = format!("dirty:{}:*", root_key);
-                let keys: Vec<String> = match conn.keys(&dirty_key).await {
-                    Ok(k) => k,
-                    Err(e) => {
-                        eprintln!("❌ Failed to get keys: {e}");
+            _ = tokio::time::sleep_until(next_due) => {
+                let now = Instant::now();
+                for (i, resource) in resources.iter().enumerate() {
+                    if due[i] > now {
                         continue;
                     }
-                };
-
-                for key in keys {
-                    println!("key : {key}");
-                    if let Ok(Some(bytes)) = conn.get::<_, Option<String>>(&key).await {
-                        // Write to DB
-                        write_function(db.clone(), bytes.clone()).await;
-
-                        let clean_key = key.strip_prefix("dirty:").unwrap_or(&key).to_string();
-
-                        // Clean up dirty key, set clean with short TTL
-                        /*let _: () = conn.del(&key).await.unwrap_or(());
-                        
-                        let _: () = conn.set_ex(&clean_key, bytes, 10).await.unwrap_or(());
-                        println!("  Write behind for : {key}");*/
-                        let ttl_sec = config.lock().unwrap().ttl_clean;
-                        /* atomic version (using redis script lua) */
-                        let script = Script::new(
-                            r#"
-                            local dirty_key = KEYS[1]
-                            local clean_key = KEYS[2]
-                            local value = ARGV[1]
-                            local ttl_sec = ARGV[2]
-                            redis.call('del', dirty_key)
-                            redis.call('setex', clean_key, ttl_sec, value)
-                            return 1
-                            "#,
-                        );
-
-                        let _: i32 = script
-                            .key(key)
-                            .key(clean_key)
-                            .arg(bytes)
-                            .arg(ttl_sec)
-                            .invoke_async(&mut conn)
-                            .await
-                            .expect("Failed to execute write-behind script");
-                    }
+                    // Followers check the lease again next interval
+                    last_flush[i] = Instant::now();
+                    flush_resource(&mut conn, &db, &keys, resource, &leases, i).await;
+                    last_flush[i] = Instant::now();
                 }
             }
             _ = token.cancelled() => {
                 println!("{} Write-behind task shutting down...", "Shutdown".red().bold());
                 // Perform one final write for all dirty keys before exiting
-                let dirty_key = format!("dirty:{}:*", root_key);
-                if let Ok(keys) = conn.keys::<_, Vec<String>>(&dirty_key).await {
-                    for key in keys {
-                        if let Ok(Some(bytes)) = conn.get::<_, Option<String>>(&key).await {
-
-                            // let _: () = conn.del(&key).await.unwrap_or(());
-                            // let clean_key = key.strip_prefix("dirty:").unwrap_or(&key).to_string();
-                            // let _: () = conn.set_ex(&clean_key, bytes, 10).await.unwrap_or(());
-                            // println!("  Final write for: {key}");
-                            let script = Script::new(
-                                r#"
-                                local dirty_key = KEYS[1]
-                                local clean_key = KEYS[2]
-                                local value = ARGV[1]
-                                local ttl_sec = tonumber(ARGV[2])
-                            
-                                redis.call('del', dirty_key)
-                                redis.call('setex', clean_key, ttl_sec, value)
-                            
-                                return 1
-                                "#
-                            );
-                            
-                            let clean_key = key.strip_prefix("dirty:").unwrap_or(&key).to_string();
-                            
-                            let _: i32 = script
-                                .key(&key)            // dirty key
-                                .key(&clean_key)      // clean key
-                                .arg(bytes.clone())   // value
-                                .arg(10)              // TTL
-                                .invoke_async(&mut conn)
-                                .await
-                                .unwrap_or(0);
-                            
-                            println!("  Final write (atomic) for: {key}");
-                            write_function(db.clone(), bytes.clone()).await;
-                        }
-                    }
+                for (i, resource) in resources.iter().enumerate() {
+                    flush_resource(&mut conn, &db, &keys, resource, &leases, i).await;
                 }
                 break;
             }
@@ -126,75 +177,514 @@ where
     }
 }
 
-/// Background task: listens for Redis expire (delete) events.
-/// On expire, invokes user-provided delete function.
-pub async fn delete_event_listener<F, Fut, DB: Database>(
-    client: redis::Client,
-    db: Pool<DB>,
-    root_key: String,
-    delete_function: F,
-    token: CancellationToken,
-)
-where
-    F: Fn(Pool<DB>, String) -> Fut,
-    Fut: Future<Output = ()>,
-{
-    let mut pubsub_conn = match client.get_async_pubsub().await {
-        Ok(conn) => conn,
+/// Flush every dirty entry of resource `i` to DB, if this replica leads it.
+///
+/// The lease is checked (and renewed when due) before every key, so a long
+/// flush keeps its lease, and stops as soon as another replica took over.
+async fn flush_resource<DB: Database>(
+    conn: &mut CacheConn,
+    db: &Pool<DB>,
+    keys: &KeySpace,
+    resource: &SyncResource<DB>,
+    leases: &Leases,
+    i: usize,
+) {
+    let Some(fence) = leases.fence(i, conn).await else {
+        return;
+    };
+
+    // Scan for dirty keys
+    let dirty_key = keys.dirty_pattern(&resource.root_key);
+    let dirty_keys: Vec<String> = match conn.keys(&dirty_key).await {
+        Ok(k) => k,
         Err(e) => {
-            eprintln!("❌ Failed to get pubsub connection: {e}");
+            eprintln!("❌ Failed to get keys: {e}");
             return;
         }
     };
 
-    //TODO : not create in here.
-    let mut conn = match client.get_multiplexed_async_connection().await {
-        Ok(conn) => conn,
-        Err(e) => {
-            eprintln!("❌ Failed to get multiplexed connection: {e}");
+    for key in dirty_keys {
+        if leases.fence(i, conn).await.as_deref() != Some(fence.as_str()) {
+            eprintln!("⚠️ Lost cache leadership for {} during flush, stopping", resource.root_key);
             return;
         }
+        println!("key : {key}");
+        flush_key(conn, db, keys, resource, &key).await;
+    }
+}
+
+/// Write one dirty entry to DB, then turn it into a clean entry that
+/// lives as long as the resource's TTL policy says.
+/// Returns `false` if it could not be written and stays dirty.
+async fn flush_key<DB: Database>(
+    conn: &mut CacheConn,
+    db: &Pool<DB>,
+    keys: &KeySpace,
+    resource: &SyncResource<DB>,
+    key: &str,
+) -> bool {
+    let version_key = keys.version_from_dirty(key).unwrap_or_else(|| format!("{key}-version"));
+    // Value and version read together (same slot in cluster mode)
+    let (bytes, version) = match conn.mget::<_, (Option<String>, Option<String>)>(&[key, &version_key]).await {
+        Ok((Some(bytes), version)) => (bytes, version.unwrap_or_default()),
+        // Already flushed or deleted
+        Ok((None, _)) => return true,
+        Err(e) => {
+            eprintln!("❌ Failed to read {key}: {e}");
+            return false;
+        }
     };
 
-    // Subscribe to Redis key expire events
-    if let Err(e) = pubsub_conn.subscribe("__keyevent@0__:expired").await {
-        eprintln!("❌ Failed to subscribe to key events: {e}");
-        return;
+    // Write to DB; on error the entry stays dirty for the next flush
+    if let Err(e) = (resource.put_function)(db.clone(), bytes.clone()).await {
+        eprintln!("❌ Failed to write {key} to DB: {e}");
+        return false;
     }
-    let mut pubsub_stream = pubsub_conn.on_message();
 
-    println!("{} Redis expired event listening", "Start".green().bold());
+    let clean_key = keys.clean_from_dirty(key).unwrap_or_else(|| key.to_string());
+    let delete_key = keys.delete_from_dirty(key).unwrap_or_else(|| format!("{key}-delete"));
+    let config = *resource.config.lock().unwrap();
+    let entity_key = keys.key_from_dirty(key).unwrap_or_else(|| key.to_string());
+    let ttl = config.clean_ttls(resource.ttl_policy.clean(&config, &TtlContext::key(&resource.root_key, &entity_key)));
+    let ttl_key = keys.ttl(&entity_key);
+
+    // Dirty → clean with short TTL, unless changed or deleted meanwhile
+    let result = entity::flush(conn, key, &clean_key, &version_key, &delete_key, &ttl_key, &bytes, &version, ttl).await;
+    match result {
+        Ok(false) => println!("key : {key} changed during flush, keeping it dirty"),
+        Ok(true) => l1::invalidate(conn, keys, &resource.l1, &config, &entity_key).await,
+        Err(e) => {
+            // Still dirty: a stream event stays pending and is reclaimed
+            eprintln!("❌ Failed to execute write-behind script: {e}");
+            return false;
+        }
+    }
+    true
+}
+
+/// Consumer group of the write streams.
+const WRITE_GROUP: &str = "cache-write";
+
+/// Delay between two reads of the write streams while they are empty.
+const WRITE_STREAM_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Events read per stream worker and round trip.
+const WRITE_STREAM_BATCH: usize = 10;
+
+/// Stream write-behind worker for resources in `WriteBehind::Stream` mode.
+///
+/// Every PUT appends the entity key to the resource's write stream. This
+/// replica reads the stream as one consumer of a shared group and applies
+/// up to `workers` events concurrently: the current dirty entry is written
+/// to DB and the event acknowledged only once that succeeded. Replicas
+/// scale out by joining the group; unacknowledged events idle for
+/// `CacheConfig::reclaim_idle` are reclaimed on startup and then every
+/// `reclaim_idle`, so delivery is at-least-once.
+pub(crate) async fn write_streams<DB: Database>(
+    mut conn: CacheConn,
+    db: Pool<DB>,
+    keys: KeySpace,
+    resources: Vec<SyncResource<DB>>,
+    token: CancellationToken,
+) {
+    let consumer = lease::instance_id();
+    let mut started = vec![false; resources.len()];
+    // Reclaim work left by a previous run first
+    let mut next_reclaim = vec![Instant::now(); resources.len()];
     loop {
-        tokio::select! {
-            Some(msg) = pubsub_stream.next() => {
-                let expired_key: String = match msg.get_payload() {
-                    Ok(key) => key,
-                    Err(_) => continue,
+        let mut applied = 0;
+        for (i, resource) in resources.iter().enumerate() {
+            // Mode may be set after the workers started (`with_config`)
+            let config = *resource.config.lock().unwrap();
+            let WriteBehind::Stream { workers } = config.write_behind else {
+                continue;
+            };
+            let stream = StreamConsumer::new(keys.dirty_stream(&resource.root_key), WRITE_GROUP, &consumer);
+            if !started[i] {
+                println!("{} Redis write stream of {} ({workers} workers)", "Start".green().bold(), resource.root_key);
+                stream.create_group(&mut conn).await;
+                started[i] = true;
+            }
+            if Instant::now() >= next_reclaim[i] {
+                next_reclaim[i] = Instant::now() + config.reclaim_idle;
+                match stream.reclaim(&mut conn, config.reclaim_idle, WRITE_STREAM_BATCH).await {
+                    Ok(entries) => applied += apply_writes(&conn, &db, &keys, resource, &stream, entries, workers).await,
+                    Err(e) => eprintln!("❌ Failed to reclaim write events: {e}"),
+                }
+            }
+            match stream.read_new(&mut conn, WRITE_STREAM_BATCH * workers.max(1)).await {
+                Ok(entries) => applied += apply_writes(&conn, &db, &keys, resource, &stream, entries, workers).await,
+                Err(e) => eprintln!("❌ Failed to read write events: {e}"),
+            }
+        }
+
+        if token.is_cancelled() && applied == 0 {
+            break;
+        }
+        if applied == 0 {
+            tokio::select! {
+                _ = tokio::time::sleep(WRITE_STREAM_POLL_INTERVAL) => {}
+                // Drain what is left, then stop
+                _ = token.cancelled() => {}
+            }
+        }
+    }
+    println!("{} Write stream task shutting down...", "Shutdown".red().bold());
+}
+
+/// Apply write events with up to `workers` in flight.
+/// Returns the number of events handled.
+async fn apply_writes<DB: Database>(
+    conn: &CacheConn,
+    db: &Pool<DB>,
+    keys: &KeySpace,
+    resource: &SyncResource<DB>,
+    stream: &StreamConsumer<'_>,
+    entries: Vec<StreamId>,
+    workers: usize,
+) -> usize {
+    let count = entries.len();
+    futures_util::stream::iter(entries)
+        .for_each_concurrent(workers.max(1), |entry| {
+            let mut conn = conn.clone();
+            async move {
+                let Some(key) = entry.get::<String>("key") else {
+                    stream.ack(&mut conn, &entry.id).await;
+                    return;
                 };
+                // Left pending on failure, retried after `reclaim_idle`
+                if flush_key(&mut conn, db, keys, resource, &keys.dirty(&key)).await {
+                    stream.ack(&mut conn, &entry.id).await;
+                }
+            }
+        })
+        .await;
+    count
+}
+
+/// Milliseconds since the Unix epoch, the score of the pending delete queue.
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Delay between two polls of the pending delete queues.
+const DELETE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Max pending deletes moved or read per resource and round trip.
+const DELETE_POLL_BATCH: usize = 100;
+
+/// Consumer group of the delete streams.
+const DELETE_GROUP: &str = "cache-delete";
+
+/// Atomically move due entries from the pending delete queue to the
+/// delete stream. Only the caller whose ZREM succeeds adds the entry.
+/// Returns -1 without moving anything if the leader lease carries
+/// another fence (the caller lost leadership).
+const MOVE_DUE_SCRIPT: &str = r#"
+local queue_key = KEYS[1]
+local stream_key = KEYS[2]
+local lock_key = KEYS[3]
+local max_score = ARGV[1]
+local count = tonumber(ARGV[2])
+local fence = ARGV[3]
+if redis.call('get', lock_key) ~= fence then
+    return -1
+end
+local ids = redis.call('zrangebyscore', queue_key, '-inf', max_score, 'LIMIT', 0, count)
+for _, id in ipairs(ids) do
+    if redis.call('zrem', queue_key, id) == 1 then
+        redis.call('xadd', stream_key, '*', 'id', id)
+    end
+end
+return #ids
+"#;
+
+/// Background task: runs pending deletes once they are due.
+///
+/// Due entries move from the queue to a per-resource Redis Stream read
+/// through a consumer group. An entry is acknowledged only after the
+/// delete function succeeds, so deletes survive failed callbacks and
+/// restarts: unacknowledged entries idle for `CacheConfig::reclaim_idle`
+/// are reclaimed on startup and then every `reclaim_idle`. Between polls it
+/// sleeps until the next delete is due. On shutdown every pending delete is executed.
+///
+/// Only the replica holding a resource's leader lease polls it; the move
+/// script checks the lease fence, so a stale leader cannot move entries.
+pub(crate) async fn delete_poller<DB: Database>(
+    mut conn: CacheConn,
+    db: Pool<DB>,
+    keys: KeySpace,
+    resources: Vec<SyncResource<DB>>,
+    leases: Arc<Leases>,
+    token: CancellationToken,
+) {
+    println!("{} Redis pending delete poller", "Start".green().bold());
+    let consumer = lease::instance_id();
+    for resource in &resources {
+        StreamConsumer::new(keys.delete_stream(&resource.root_key), DELETE_GROUP, &consumer)
+            .create_group(&mut conn)
+            .await;
+    }
+
+    // Reclaim work left by a previous run first
+    let mut next_reclaim = vec![Instant::now(); resources.len()];
+    loop {
+        let mut next_due = now_millis() + DELETE_POLL_INTERVAL.as_millis() as u64;
+        for (i, resource) in resources.iter().enumerate() {
+            let Some(fence) = leases.fence(i, &mut conn).await else {
+                continue;
+            };
+            let pending = PendingDeletes {
+                keys: &keys,
+                resource,
+                stream: StreamConsumer::new(keys.delete_stream(&resource.root_key), DELETE_GROUP, &consumer),
+                lock_key: leases.lock_key(i),
+                fence: &fence,
+            };
+            pending.move_due(&mut conn, Some(now_millis())).await;
+            pending.read_new(&mut conn, &db).await;
+            if Instant::now() >= next_reclaim[i] {
+                let idle = resource.config.lock().unwrap().reclaim_idle;
+                next_reclaim[i] = Instant::now() + idle;
+                pending.reclaim(&mut conn, &db, idle).await;
+            }
+            if let Some(due) = pending.next_due(&mut conn).await {
+                next_due = next_due.min(due);
+            }
+        }
+
+        let wait = Duration::from_millis(next_due.saturating_sub(now_millis()));
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = token.cancelled() => break,
+        }
+    }
 
-                let prefix = format!("delete:{}:", root_key);
-                if let Some(post_id_str) = expired_key.strip_prefix(&prefix) {
-                    // Call delete handler
-                    delete_function(db.clone(), post_id_str.to_string()).await;
+    println!("{} Delete poller shutting down...", "Shutdown".red().bold());
+    for (i, resource) in resources.iter().enumerate() {
+        let Some(fence) = leases.fence(i, &mut conn).await else {
+            continue;
+        };
+        let pending = PendingDeletes {
+            keys: &keys,
+            resource,
+            stream: StreamConsumer::new(keys.delete_stream(&resource.root_key), DELETE_GROUP, &consumer),
+            lock_key: leases.lock_key(i),
+            fence: &fence,
+        };
+        pending.move_due(&mut conn, None).await;
+        pending.read_new(&mut conn, &db).await;
+    }
+}
+
+/// Pending deletes of one resource, processed under the leader lease
+/// `lock_key` holding `fence`.
+struct PendingDeletes<'a, DB: Database> {
+    keys: &'a KeySpace,
+    resource: &'a SyncResource<DB>,
+    stream: StreamConsumer<'a>,
+    lock_key: &'a str,
+    fence: &'a str,
+}
+
+impl<DB: Database> PendingDeletes<'_, DB> {
+    /// Move entries due by `due` (all if `None`) to the delete stream.
+    async fn move_due(&self, conn: &mut CacheConn, due: Option<u64>) {
+        let max = due.map_or_else(|| "+inf".to_string(), |due| due.to_string());
+        loop {
+            let moved: redis::RedisResult<i64> = Script::new(MOVE_DUE_SCRIPT)
+                .key(self.keys.delete_queue(&self.resource.root_key))
+                .key(&self.stream.stream)
+                .key(self.lock_key)
+                .arg(&max)
+                .arg(DELETE_POLL_BATCH)
+                .arg(self.fence)
+                .invoke_async(conn)
+                .await;
+            match moved {
+                Ok(moved) if moved == DELETE_POLL_BATCH as i64 => continue,
+                Ok(-1) => {
+                    eprintln!("⚠️ Lost cache leadership for {}, not moving deletes", self.resource.root_key);
+                    return;
+                }
+                Ok(_) => return,
+                Err(e) => {
+                    eprintln!("❌ Failed to move due deletes: {e}");
+                    return;
                 }
             }
-            _ = token.cancelled() => {
-                println!("{} Delete event listener shutting down...", "Shutdown".red().bold());
-                let delete_key = format!("delete:{}:*", root_key);
-                if let Ok(keys) = conn.keys::<_, Vec<String>>(&delete_key).await {
-                    let prefix = format!("delete:{}:", root_key);
-                    for key in keys {
-                        if let Some(post_id_str) = key.strip_prefix(&prefix) {
-                            // Call delete handler
-                            delete_function(db.clone(), post_id_str.to_string()).await;
-                            let _: () = conn.del(&key).await.unwrap_or(());
-                            println!("Final delete for: {key}");
-                        }
-                    }
-                    
-                break;
+        }
+    }
+
+    /// Due time (ms) of the earliest queued delete, if any.
+    async fn next_due(&self, conn: &mut CacheConn) -> Option<u64> {
+        let first: redis::RedisResult<Vec<(String, u64)>> = conn
+            .zrange_withscores(self.keys.delete_queue(&self.resource.root_key), 0, 0)
+            .await;
+        first.ok()?.first().map(|(_, due)| *due)
+    }
+
+    /// Run deletes added to the stream and not yet delivered to any consumer.
+    async fn read_new(&self, conn: &mut CacheConn, db: &Pool<DB>) {
+        loop {
+            let entries = match self.stream.read_new(conn, DELETE_POLL_BATCH).await {
+                Ok(entries) => entries,
+                Err(e) => {
+                    eprintln!("❌ Failed to read pending deletes: {e}");
+                    return;
+                }
+            };
+            if entries.is_empty() {
+                return;
+            }
+            self.run(conn, db, entries).await;
+        }
+    }
+
+    /// Claim and retry deletes left unacknowledged for at least `idle`.
+    async fn reclaim(&self, conn: &mut CacheConn, db: &Pool<DB>, idle: Duration) {
+        match self.stream.reclaim(conn, idle, DELETE_POLL_BATCH).await {
+            Ok(entries) => {
+                if !entries.is_empty() {
+                    println!("Reclaimed {} pending deletes of {}", entries.len(), self.resource.root_key);
+                }
+                self.run(conn, db, entries).await;
+            }
+            Err(e) => eprintln!("❌ Failed to reclaim pending deletes: {e}"),
+        }
+    }
+
+    /// Call the delete function for each entry; acknowledge only successes
+    /// and clear their delete markers.
+    async fn run(&self, conn: &mut CacheConn, db: &Pool<DB>, entries: Vec<StreamId>) {
+        for entry in entries {
+            let Some(id) = entry.get::<String>("id") else {
+                self.stream.ack(conn, &entry.id).await;
+                continue;
+            };
+            // Call delete handler
+            if let Err(e) = (self.resource.delete_function)(db.clone(), id.clone()).await {
+                eprintln!("❌ Failed to delete {}:{id} from DB, will retry: {e}", self.resource.root_key);
+                continue;
+            }
+            self.stream.ack(conn, &entry.id).await;
+            let ttl = self.resource.config.lock().unwrap().ttl_clean;
+            let key = format!("{}:{}", self.resource.root_key, id);
+            if let Err(e) = entity::forget_deleted(conn, self.keys, &key, ttl).await {
+                eprintln!("❌ Failed to clear delete marker of {key}: {e}");
+            }
+        }
+    }
+}
+
+/// Keyevent channel for evictions in the client's logical DB (ex: "__keyevent@3__:evicted").
+fn evicted_channel(client: &redis::Client) -> String {
+    format!("__keyevent@{}__:evicted", client.get_connection_info().redis.db)
+}
+
+/// Delay before resubscribing after a keyevent subscription is lost.
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+/// Subscription loop shared by the keyevent listeners: passes the key of
+/// every event on `channel` to `on_event` until `token` is cancelled.
+///
+/// `label` names the events in logs (ex: "Eviction"). When the subscription
+/// ends, on any one master in cluster mode, it reconnects, turns keyspace
+/// events back on and resubscribes to the current masters.
+async fn keyevent_loop(
+    client: &redis::Client,
+    conn: &mut CacheConn,
+    channel: &str,
+    label: &str,
+    token: &CancellationToken,
+    mut on_event: impl FnMut(&CacheConn, &str),
+) {
+    loop {
+        // Subscribe to the key events of the selected logical DB
+        // (every master in cluster mode)
+        let subscribed = tokio::select! {
+            result = connection::subscribe_all(client, conn, channel) => result,
+            _ = token.cancelled() => break,
+        };
+        let pubsubs = match subscribed {
+            Ok(pubsubs) => pubsubs,
+            Err(e) => {
+                eprintln!("❌ Failed to subscribe to {} events: {e}", label.to_lowercase());
+                tokio::select! {
+                    _ = tokio::time::sleep(RESUBSCRIBE_DELAY) => continue,
+                    _ = token.cancelled() => break,
+                }
+            }
+        };
+        let mut pubsub_stream = connection::merge_messages(pubsubs);
+
+        let cancelled = loop {
+            tokio::select! {
+                msg = pubsub_stream.next() => {
+                    let Some(msg) = msg else { break false };
+                    let Ok(key) = msg.get_payload::<String>() else { continue };
+                    on_event(conn, &key);
                 }
+                _ = token.cancelled() => break true,
             }
+        };
+        if cancelled {
+            break;
+        }
+
+        let count = conn.count_resubscribe();
+        eprintln!("⚠️ {label} event subscription lost, resubscribing... (resubscribe #{count})");
+        tokio::select! {
+            _ = tokio::time::sleep(RESUBSCRIBE_DELAY) => {}
+            _ = token.cancelled() => break,
+        }
+        if let Err(e) = conn.refresh().await {
+            eprintln!("❌ Failed to reconnect to Redis: {e}");
+            continue;
+        }
+        if let Err(e) = connection::enable_expire_events(conn).await {
+            eprintln!("❌ Failed to set Redis config (PubSub): {e}");
         }
     }
 }
+
+/// Background task: listens for Redis eviction events.
+/// A dirty entry evicted before its flush is a lost write: it is logged,
+/// counted in `CacheConn::evicted_dirty_count` and passed to `on_dirty_evicted`.
+pub(crate) async fn eviction_listener<DB: Database>(
+    client: redis::Client,
+    mut conn: CacheConn,
+    keys: KeySpace,
+    resources: Vec<SyncResource<DB>>,
+    on_dirty_evicted: Option<EvictionCallback>,
+    token: CancellationToken,
+) {
+    let channel = evicted_channel(&client);
+    println!("{} Redis evicted event listening", "Start".green().bold());
+    keyevent_loop(&client, &mut conn, &channel, "Eviction", &token, |conn, evicted_key| {
+        let Some(key) = dirty_entity(&keys, &resources, evicted_key) else { return };
+        let count = conn.count_evicted_dirty();
+        eprintln!("❌ Dirty entry {key} evicted by Redis before its flush, write lost (evicted #{count})");
+        if let Some(callback) = &on_dirty_evicted {
+            callback(&key);
+        }
+    })
+    .await;
+    println!("{} Eviction listener shutting down...", "Shutdown".red().bold());
+}
+
+/// Entity key of `evicted_key` if it is a dirty entry of one of the resources.
+fn dirty_entity<DB: Database>(
+    keys: &KeySpace,
+    resources: &[SyncResource<DB>],
+    evicted_key: &str,
+) -> Option<String> {
+    resources
+        .iter()
+        .any(|resource| evicted_key.starts_with(&keys.dirty_root(&resource.root_key)))
+        .then(|| keys.key_from_dirty(evicted_key))
+        .flatten()
+}
@@ -0,0 +1,411 @@
+// src/connection.rs
+
+use redis::aio::{ConnectionLike, MultiplexedConnection, PubSub};
+use futures_util::{future, stream, Stream, StreamExt};
+use redis::{Client, Cmd, Msg, Pipeline, RedisFuture, RedisResult, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::time::{Duration, Instant};
+
+#[cfg(feature = "cluster")]
+use redis::cluster_async::ClusterConnection;
+
+/// Minimum delay between two reconnect attempts while Redis is down.
+const RECONNECT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Redis connection shared by the middleware and the background workers.
+/// - single server: reconnects automatically when the connection drops
+/// - Sentinel (cargo feature `sentinel`): same, re-discovering the master
+/// - Cluster (cargo feature `cluster`): the cluster client reconnects itself
+///
+/// Implements `ConnectionLike`, so every `AsyncCommands` method works on it.
+/// Clones share the connection and its counters.
+#[derive(Clone)]
+pub struct CacheConn {
+    kind: ConnKind,
+    stats: Arc<ConnStats>,
+    expire_events: bool,
+}
+
+#[derive(Clone)]
+enum ConnKind {
+    Single(ReconnectingConn),
+    #[cfg(feature = "cluster")]
+    Cluster(ClusterConnection),
+}
+
+#[derive(Debug, Default)]
+struct ConnStats {
+    reconnects: AtomicU64,
+    resubscribes: AtomicU64,
+    evicted_dirty: AtomicU64,
+}
+
+impl CacheConn {
+    /// Wrap a connection to one server; `client` is used to reconnect.
+    /// With `expire_events`, notifications are enabled again after a reconnect.
+    pub(crate) fn single(client: Client, conn: MultiplexedConnection, expire_events: bool) -> Self {
+        let stats = Arc::new(ConnStats::default());
+        let source = Source::Direct(client.clone());
+        CacheConn {
+            kind: ConnKind::Single(ReconnectingConn::new(source, client, conn, Arc::clone(&stats), expire_events)),
+            stats,
+            expire_events,
+        }
+    }
+
+    /// Discover the master through Sentinel and connect to it.
+    #[cfg(feature = "sentinel")]
+    pub(crate) async fn sentinel(
+        mut sentinel: redis::sentinel::SentinelClient,
+        expire_events: bool,
+    ) -> RedisResult<Self> {
+        let client = sentinel.async_get_client().await?;
+        let mut conn = client.get_multiplexed_async_connection().await?;
+        if expire_events {
+            try_enable_expire_events(&mut conn).await;
+        }
+        let stats = Arc::new(ConnStats::default());
+        let source = Source::Sentinel(Box::new(sentinel));
+        Ok(CacheConn {
+            kind: ConnKind::Single(ReconnectingConn::new(source, client, conn, Arc::clone(&stats), expire_events)),
+            stats,
+            expire_events,
+        })
+    }
+
+    #[cfg(feature = "cluster")]
+    pub(crate) fn cluster(conn: ClusterConnection, expire_events: bool) -> Self {
+        CacheConn {
+            kind: ConnKind::Cluster(conn),
+            stats: Arc::new(ConnStats::default()),
+            expire_events,
+        }
+    }
+
+    /// Whether deletes are accelerated by keyspace notifications.
+    pub(crate) fn expire_events(&self) -> bool {
+        self.expire_events
+    }
+
+    /// Client of the server in use (the current master behind Sentinel).
+    /// `None` in cluster mode.
+    pub fn current_client(&self) -> Option<Client> {
+        match &self.kind {
+            ConnKind::Single(conn) => Some(conn.current().0),
+            #[cfg(feature = "cluster")]
+            ConnKind::Cluster(_) => None,
+        }
+    }
+
+    /// Number of automatic reconnects since startup.
+    pub fn reconnect_count(&self) -> u64 {
+        self.stats.reconnects.load(Ordering::Relaxed)
+    }
+
+    /// Number of keyevent listener resubscriptions since startup.
+    pub fn resubscribe_count(&self) -> u64 {
+        self.stats.resubscribes.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn count_resubscribe(&self) -> u64 {
+        self.stats.resubscribes.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Number of dirty entries evicted by Redis before their flush since startup.
+    pub fn evicted_dirty_count(&self) -> u64 {
+        self.stats.evicted_dirty.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn count_evicted_dirty(&self) -> u64 {
+        self.stats.evicted_dirty.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Reconnect after a keyevent listener lost its subscription.
+    /// Skipped if another task already reconnected in the meantime.
+    pub(crate) async fn refresh(&self) -> RedisResult<()> {
+        match &self.kind {
+            ConnKind::Single(conn) => conn.reconnect(conn.current().2).await,
+            #[cfg(feature = "cluster")]
+            ConnKind::Cluster(_) => Ok(()),
+        }
+    }
+}
+
+impl ConnectionLike for CacheConn {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        match &mut self.kind {
+            ConnKind::Single(conn) => conn.req_packed_command(cmd),
+            #[cfg(feature = "cluster")]
+            ConnKind::Cluster(conn) => conn.req_packed_command(cmd),
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        match &mut self.kind {
+            ConnKind::Single(conn) => conn.req_packed_commands(cmd, offset, count),
+            #[cfg(feature = "cluster")]
+            ConnKind::Cluster(conn) => conn.req_packed_commands(cmd, offset, count),
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match &self.kind {
+            ConnKind::Single(conn) => conn.current().1.get_db(),
+            #[cfg(feature = "cluster")]
+            ConnKind::Cluster(conn) => conn.get_db(),
+        }
+    }
+}
+
+/// Enable evicted key notifications (`Ee`) on the connected server(s).
+///
+/// The flags are added to the current ones, so notifications other
+/// applications rely on stay enabled. Nothing is written if they are
+/// already on (ex: set through a managed Redis parameter group).
+pub(crate) async fn enable_expire_events(conn: &mut impl ConnectionLike) -> RedisResult<()> {
+    let current: HashMap<String, String> = redis::cmd("CONFIG")
+        .arg("GET")
+        .arg("notify-keyspace-events")
+        .query_async(conn)
+        .await?;
+    let current = current.get("notify-keyspace-events").map_or("", String::as_str);
+    let Some(flags) = merge_expire_flags(current) else {
+        return Ok(());
+    };
+    redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("notify-keyspace-events")
+        .arg(flags)
+        .query_async(conn)
+        .await
+}
+
+/// Like `enable_expire_events`, but only warns on failure: managed Redis
+/// often forbids `CONFIG`, and only eviction reports depend on it.
+pub(crate) async fn try_enable_expire_events(conn: &mut impl ConnectionLike) {
+    if let Err(e) = enable_expire_events(conn).await {
+        eprintln!("⚠️ Could not enable keyspace notifications, evictions go unreported: {e}");
+    }
+}
+
+/// `current` plus `E` and `e`, or `None` if both are already covered.
+fn merge_expire_flags(current: &str) -> Option<String> {
+    let mut flags = current.to_string();
+    if !flags.contains('E') {
+        flags.push('E');
+    }
+    // `A` is an alias for every key event class, `e` included
+    if !flags.contains('e') && !flags.contains('A') {
+        flags.push('e');
+    }
+    (flags != current).then_some(flags)
+}
+
+/// Subscribe to `channel` on every node that publishes it.
+///
+/// Keyspace notifications are node-local, so in cluster mode every master
+/// gets its own pub/sub connection; `client` is the seed for their settings.
+#[cfg_attr(not(feature = "cluster"), allow(unused_variables))]
+pub(crate) async fn subscribe_all(
+    client: &Client,
+    conn: &mut CacheConn,
+    channel: &str,
+) -> RedisResult<Vec<PubSub>> {
+    let clients = match &conn.kind {
+        ConnKind::Single(single) => vec![single.current().0],
+        #[cfg(feature = "cluster")]
+        ConnKind::Cluster(_) => cluster_master_clients(client, conn).await?,
+    };
+
+    let mut pubsubs = Vec::with_capacity(clients.len());
+    for client in clients {
+        let mut pubsub = client.get_async_pubsub().await?;
+        pubsub.subscribe(channel).await?;
+        pubsubs.push(pubsub);
+    }
+    Ok(pubsubs)
+}
+
+/// Messages of every connection returned by `subscribe_all`, merged.
+///
+/// The merged stream ends as soon as any one connection ends (ex: a
+/// cluster master failed over), so the caller resubscribes to the current
+/// masters instead of losing that node's events for good.
+pub(crate) fn merge_messages(pubsubs: Vec<PubSub>) -> impl Stream<Item = Msg> + Unpin {
+    let streams = pubsubs.into_iter().map(|pubsub| {
+        // `None` marks the end of one connection
+        pubsub.into_on_message().map(Some).chain(stream::iter([None]))
+    });
+    stream::select_all(streams)
+        .take_while(|msg| future::ready(msg.is_some()))
+        .filter_map(future::ready)
+}
+
+/// One client per cluster master, discovered with `CLUSTER NODES`.
+/// Connection settings (auth, TLS) are copied from the seed `client`.
+#[cfg(feature = "cluster")]
+async fn cluster_master_clients(client: &Client, conn: &mut CacheConn) -> RedisResult<Vec<Client>> {
+    use redis::ConnectionAddr;
+
+    let nodes: String = redis::cmd("CLUSTER").arg("NODES").query_async(conn).await?;
+    let seed = client.get_connection_info().clone();
+
+    let mut clients = Vec::new();
+    for line in nodes.lines() {
+        // <id> <ip:port@cport[,hostname]> <flags> ...
+        let mut fields = line.split_whitespace();
+        let (Some(_), Some(addr), Some(flags)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        if !flags.split(',').any(|f| f == "master") || flags.contains("fail") {
+            continue;
+        }
+        let addr = addr.split('@').next().unwrap_or(addr);
+        let Some((host, port)) = addr.rsplit_once(':') else {
+            continue;
+        };
+        let Ok(port) = port.parse::<u16>() else {
+            continue;
+        };
+
+        let mut info = seed.clone();
+        info.addr = match seed.addr {
+            ConnectionAddr::TcpTls { ref insecure, ref tls_params, .. } => ConnectionAddr::TcpTls {
+                host: host.to_string(),
+                port,
+                insecure: *insecure,
+                tls_params: tls_params.clone(),
+            },
+            _ => ConnectionAddr::Tcp(host.to_string(), port),
+        };
+        clients.push(Client::open(info)?);
+    }
+    Ok(clients)
+}
+
+/// Where a reconnect finds the server.
+enum Source {
+    Direct(Client),
+    #[cfg(feature = "sentinel")]
+    Sentinel(Box<redis::sentinel::SentinelClient>),
+}
+
+/// Multiplexed connection that replaces itself when it drops.
+///
+/// On a dropped connection or a `READONLY` reply (old Sentinel master
+/// demoted) the server is resolved again, notifications are re-enabled on
+/// it and the command is retried once. Concurrent failures share one
+/// reconnect: only the first caller for a given generation reconnects.
+#[derive(Clone)]
+struct ReconnectingConn {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    // source + time of the last attempt
+    source: tokio::sync::Mutex<(Source, Option<Instant>)>,
+    current: RwLock<(Client, MultiplexedConnection, u64)>,
+    stats: Arc<ConnStats>,
+    expire_events: bool,
+}
+
+impl ReconnectingConn {
+    fn new(
+        source: Source,
+        client: Client,
+        conn: MultiplexedConnection,
+        stats: Arc<ConnStats>,
+        expire_events: bool,
+    ) -> Self {
+        ReconnectingConn {
+            shared: Arc::new(Shared {
+                source: tokio::sync::Mutex::new((source, None)),
+                current: RwLock::new((client, conn, 0)),
+                stats,
+                expire_events,
+            }),
+        }
+    }
+
+    /// Current client, connection and generation.
+    fn current(&self) -> (Client, MultiplexedConnection, u64) {
+        self.shared.current.read().unwrap().clone()
+    }
+
+    /// Replace the connection, unless it changed since generation `seen`.
+    async fn reconnect(&self, seen: u64) -> RedisResult<()> {
+        let mut source = self.shared.source.lock().await;
+        if self.current().2 != seen {
+            return Ok(());
+        }
+        if let Some(last) = source.1 {
+            let wait = RECONNECT_INTERVAL.saturating_sub(last.elapsed());
+            tokio::time::sleep(wait).await;
+        }
+        source.1 = Some(Instant::now());
+
+        let client = match &mut source.0 {
+            Source::Direct(client) => client.clone(),
+            #[cfg(feature = "sentinel")]
+            Source::Sentinel(sentinel) => sentinel.async_get_client().await?,
+        };
+        let mut conn = client.get_multiplexed_async_connection().await?;
+        if self.shared.expire_events {
+            try_enable_expire_events(&mut conn).await;
+        }
+
+        let addr = client.get_connection_info().addr.to_string();
+        {
+            let mut current = self.shared.current.write().unwrap();
+            *current = (client, conn, seen + 1);
+        }
+        let count = self.shared.stats.reconnects.fetch_add(1, Ordering::Relaxed) + 1;
+        eprintln!("⚠️ Redis reconnected to {addr} (reconnect #{count})");
+        Ok(())
+    }
+
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            let (_, mut conn, generation) = self.current();
+            match conn.req_packed_command(cmd).await {
+                Err(e) if is_reconnect_error(&e) => {
+                    self.reconnect(generation).await?;
+                    self.current().1.req_packed_command(cmd).await
+                }
+                result => result,
+            }
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            let (_, mut conn, generation) = self.current();
+            match conn.req_packed_commands(cmd, offset, count).await {
+                Err(e) if is_reconnect_error(&e) => {
+                    self.reconnect(generation).await?;
+                    self.current().1.req_packed_commands(cmd, offset, count).await
+                }
+                result => result,
+            }
+        })
+    }
+}
+
+fn is_reconnect_error(e: &redis::RedisError) -> bool {
+    e.is_connection_dropped()
+        || e.is_io_error()
+        || e.is_connection_refusal()
+        || e.kind() == redis::ErrorKind::ReadOnly
+}
@@ -0,0 +1,480 @@
+// src/entity.rs
+
+//! Cache state machine of one entity (ex: "posts:1").
+//!
+//! | state     | keys                                        |
+//! |-----------|---------------------------------------------|
+//! | `Miss`    | none                                        |
+//! | `Clean`   | clean entry + its TTLs (same TTL)           |
+//! | `Dirty`   | dirty entry + version, no clean entry       |
+//! | `Deleted` | delete marker + version, nothing else       |
+//! | `NotFound`| missing marker (TTL), nothing else          |
+//!
+//! Transitions, each one Lua script so concurrent requests and background
+//! workers never see or leave a half-applied state:
+//! - read (GET, PUT): `Deleted` > `Dirty` > `Clean` > `NotFound` > `Miss`, with the version
+//! - fill (handler 200 on a miss, refresh-ahead): `Miss`/`Clean` → `Clean`;
+//!   skipped if `Dirty`, `Deleted` or the version changed since the read
+//! - mark missing (handler 404 on a GET miss): `Miss` → `NotFound`, same guards as fill
+//! - forget missing (PUT, create): `NotFound` → `Miss`
+//! - mark dirty (PUT hit): `Clean`/`Dirty` → `Dirty`, only if the version read is
+//!   unchanged (the PUT merges again on conflict)
+//! - commit (write-through PUT hit, after the DB write): `Clean`/`Dirty` → `Clean`
+//!   if the version read is unchanged (the PUT merges and writes again on conflict)
+//! - mark deleted (DELETE): any → `Deleted`, pending delete queued
+//! - flush (write-behind): `Dirty` → `Clean`, only if the version is unchanged
+//!   and not `Deleted`
+//! - forget deleted (DB delete acknowledged): `Deleted` → `Miss`
+//!
+//! The version counter only grows while it exists; a flush leaves it to
+//! expire with the clean entry and a delete keeps it until the DB delete is
+//! done, instead of resetting it.
+
+use redis::{AsyncCommands, RedisResult, Script};
+
+use crate::connection::CacheConn;
+use crate::keys::KeySpace;
+
+/// State of an entity as seen by one read.
+pub(crate) enum Entry {
+    Miss,
+    Clean(String),
+    Dirty(String),
+    Deleted,
+    NotFound,
+}
+
+/// One read: state, version ('' when absent), the remaining TTL of
+/// the clean entry in ms (negative without one) and the TTLs it was
+/// stored with (`None` if unknown).
+pub(crate) struct Snapshot {
+    pub entry: Entry,
+    pub version: String,
+    pub clean_ttl_ms: i64,
+    pub stored: Option<CleanTtl>,
+}
+
+/// TTLs (seconds) of a clean entry: fresh for `fresh`, then kept as a
+/// stale copy until `expiry` (fresh + stale grace), when Redis drops it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CleanTtl {
+    pub fresh: u64,
+    pub expiry: u64,
+}
+
+impl CleanTtl {
+    /// Parse the `{fresh}:{expiry}` value stored next to a clean entry.
+    fn parse(stored: &str) -> Option<CleanTtl> {
+        let (fresh, expiry) = stored.split_once(':')?;
+        Some(CleanTtl { fresh: fresh.parse().ok()?, expiry: expiry.parse().ok()? })
+    }
+}
+
+/// Outcome of `mark_dirty` and `commit`.
+pub(crate) enum MarkDirty {
+    Done,
+    /// Another PUT or a flush changed the entity since it was read.
+    Conflict,
+    Deleted,
+}
+
+const READ_SCRIPT: &str = r#"
+local delete_key = KEYS[1]
+local dirty_key = KEYS[2]
+local clean_key = KEYS[3]
+local version_key = KEYS[4]
+local missing_key = KEYS[5]
+local ttl_key = KEYS[6]
+if redis.call('exists', delete_key) == 1 then
+    return {'deleted', '', '', -2, ''}
+end
+local version = redis.call('get', version_key) or ''
+local dirty = redis.call('get', dirty_key)
+if dirty then
+    return {'dirty', dirty, version, -2, ''}
+end
+local clean = redis.call('get', clean_key)
+if clean then
+    return {'clean', clean, version, redis.call('pttl', clean_key), redis.call('get', ttl_key) or ''}
+end
+if redis.call('exists', missing_key) == 1 then
+    return {'not_found', '', version, -2, ''}
+end
+return {'miss', '', version, -2, ''}
+"#;
+
+const FILL_SCRIPT: &str = r#"
+local delete_key = KEYS[1]
+local dirty_key = KEYS[2]
+local clean_key = KEYS[3]
+local version_key = KEYS[4]
+local missing_key = KEYS[5]
+local ttl_key = KEYS[6]
+local value = ARGV[1]
+local ttl_sec = tonumber(ARGV[2])
+local version = ARGV[3]
+local fresh = ARGV[4]
+if redis.call('exists', delete_key) == 1 or redis.call('exists', dirty_key) == 1 then
+    return 0
+end
+if (redis.call('get', version_key) or '') ~= version then
+    return 0
+end
+redis.call('setex', clean_key, ttl_sec, value)
+redis.call('setex', ttl_key, ttl_sec, fresh .. ':' .. ttl_sec)
+redis.call('del', missing_key)
+return 1
+"#;
+
+/// Remember a 404 on a miss, unless the entity appeared meanwhile.
+const MARK_MISSING_SCRIPT: &str = r#"
+local delete_key = KEYS[1]
+local dirty_key = KEYS[2]
+local clean_key = KEYS[3]
+local version_key = KEYS[4]
+local missing_key = KEYS[5]
+local ttl_sec = tonumber(ARGV[1])
+local version = ARGV[2]
+if redis.call('exists', delete_key) == 1 or redis.call('exists', dirty_key) == 1
+    or redis.call('exists', clean_key) == 1 then
+    return 0
+end
+if (redis.call('get', version_key) or '') ~= version then
+    return 0
+end
+redis.call('setex', missing_key, ttl_sec, '1')
+return 1
+"#;
+
+/// Returns the new version, -1 on version conflict, -2 if deleted.
+/// KEYS[5] (write event stream) is only passed when it can share the
+/// script, i.e. outside Redis Cluster.
+const MARK_DIRTY_SCRIPT: &str = r#"
+local delete_key = KEYS[1]
+local dirty_key = KEYS[2]
+local clean_key = KEYS[3]
+local version_key = KEYS[4]
+local stream_key = KEYS[5]
+local value = ARGV[1]
+local expected = ARGV[2]
+local key = ARGV[3]
+if redis.call('exists', delete_key) == 1 then
+    return -2
+end
+if (redis.call('get', version_key) or '') ~= expected then
+    return -1
+end
+redis.call('set', dirty_key, value)
+redis.call('del', clean_key)
+local version = redis.call('incr', version_key)
+redis.call('persist', version_key)
+if stream_key then
+    redis.call('xadd', stream_key, '*', 'key', key)
+end
+return version
+"#;
+
+/// Same guards as `MARK_DIRTY_SCRIPT`, but the value is already in DB.
+/// On conflict nothing changes: the PUT merges again on top of the newer
+/// state and writes that through, so the DB ends with both writes' fields.
+/// A 0 TTL ("don't cache") drops the clean entry, and the version expires
+/// after 1 second.
+const COMMIT_SCRIPT: &str = r#"
+local delete_key = KEYS[1]
+local dirty_key = KEYS[2]
+local clean_key = KEYS[3]
+local version_key = KEYS[4]
+local ttl_key = KEYS[5]
+local value = ARGV[1]
+local expected = ARGV[2]
+local ttl_sec = tonumber(ARGV[3])
+local fresh = ARGV[4]
+if redis.call('exists', delete_key) == 1 then
+    return -2
+end
+if (redis.call('get', version_key) or '') ~= expected then
+    return -1
+end
+redis.call('del', dirty_key)
+if ttl_sec > 0 then
+    redis.call('setex', clean_key, ttl_sec, value)
+    redis.call('setex', ttl_key, ttl_sec, fresh .. ':' .. ttl_sec)
+else
+    redis.call('del', clean_key)
+end
+local version = redis.call('incr', version_key)
+redis.call('expire', version_key, math.max(ttl_sec, 1))
+return version
+"#;
+
+/// KEYS[5] (pending delete queue) is only passed when it can share the
+/// script, i.e. outside Redis Cluster.
+const MARK_DELETED_SCRIPT: &str = r#"
+local delete_key = KEYS[1]
+local dirty_key = KEYS[2]
+local clean_key = KEYS[3]
+local version_key = KEYS[4]
+local queue_key = KEYS[5]
+local id = ARGV[1]
+local due = ARGV[2]
+redis.call('del', dirty_key, clean_key)
+-- Keep counting, so a write after the delete never reuses an old version
+redis.call('incr', version_key)
+redis.call('persist', version_key)
+redis.call('set', delete_key, '1')
+if queue_key then
+    redis.call('zadd', queue_key, due, id)
+end
+return 1
+"#;
+
+/// Leaves the version to expire like a flush does, instead of resetting it.
+const FORGET_DELETED_SCRIPT: &str = r#"
+local delete_key = KEYS[1]
+local version_key = KEYS[2]
+local ttl_sec = tonumber(ARGV[1])
+redis.call('del', delete_key)
+redis.call('expire', version_key, math.max(ttl_sec, 1))
+return 1
+"#;
+
+/// Returns 0 and leaves the newer dirty entry for the next flush if the
+/// version changed, or nothing if the entity was deleted meanwhile.
+/// A 0 TTL ("don't cache") leaves no clean entry, like `COMMIT_SCRIPT`.
+const FLUSH_SCRIPT: &str = r#"
+local dirty_key = KEYS[1]
+local clean_key = KEYS[2]
+local version_key = KEYS[3]
+local delete_key = KEYS[4]
+local ttl_key = KEYS[5]
+local value = ARGV[1]
+local ttl_sec = tonumber(ARGV[2])
+local version = ARGV[3]
+local fresh = ARGV[4]
+if redis.call('exists', delete_key) == 1 then
+    return 0
+end
+if (redis.call('get', version_key) or '') ~= version then
+    return 0
+end
+redis.call('del', dirty_key)
+if ttl_sec > 0 then
+    redis.call('setex', clean_key, ttl_sec, value)
+    redis.call('setex', ttl_key, ttl_sec, fresh .. ':' .. ttl_sec)
+end
+if version ~= '' then
+    redis.call('expire', version_key, math.max(ttl_sec, 1))
+end
+return 1
+"#;
+
+/// Read the state of `key` and its version.
+pub(crate) async fn read(conn: &mut CacheConn, keys: &KeySpace, key: &str) -> RedisResult<Snapshot> {
+    let (state, value, version, clean_ttl_ms, stored): (String, String, String, i64, String) = Script::new(READ_SCRIPT)
+        .key(keys.delete(key))
+        .key(keys.dirty(key))
+        .key(keys.clean(key))
+        .key(keys.version(key))
+        .key(keys.missing(key))
+        .key(keys.ttl(key))
+        .invoke_async(conn)
+        .await?;
+    let entry = match state.as_str() {
+        "deleted" => Entry::Deleted,
+        "not_found" => Entry::NotFound,
+        "dirty" => Entry::Dirty(value),
+        "clean" => Entry::Clean(value),
+        _ => Entry::Miss,
+    };
+    Ok(Snapshot { entry, version, clean_ttl_ms, stored: CleanTtl::parse(&stored) })
+}
+
+/// Cache a handler response as clean, unless dirty, deleted or
+/// changed (`version` read before the handler ran) meanwhile.
+pub(crate) async fn fill(
+    conn: &mut CacheConn,
+    keys: &KeySpace,
+    key: &str,
+    value: &str,
+    version: &str,
+    ttl: CleanTtl,
+) -> RedisResult<bool> {
+    Script::new(FILL_SCRIPT)
+        .key(keys.delete(key))
+        .key(keys.dirty(key))
+        .key(keys.clean(key))
+        .key(keys.version(key))
+        .key(keys.missing(key))
+        .key(keys.ttl(key))
+        .arg(value)
+        .arg(ttl.expiry)
+        .arg(version)
+        .arg(ttl.fresh)
+        .invoke_async(conn)
+        .await
+}
+
+/// Remember that the handler answered 404 for `key`, unless it
+/// appeared or changed (`version`) meanwhile.
+pub(crate) async fn mark_missing(
+    conn: &mut CacheConn,
+    keys: &KeySpace,
+    key: &str,
+    version: &str,
+    ttl_sec: u64,
+) -> RedisResult<bool> {
+    Script::new(MARK_MISSING_SCRIPT)
+        .key(keys.delete(key))
+        .key(keys.dirty(key))
+        .key(keys.clean(key))
+        .key(keys.version(key))
+        .key(keys.missing(key))
+        .arg(ttl_sec)
+        .arg(version)
+        .invoke_async(conn)
+        .await
+}
+
+/// Drop the 404 marker of `key` (PUT, create).
+pub(crate) async fn forget_missing(conn: &mut CacheConn, keys: &KeySpace, key: &str) -> RedisResult<()> {
+    let _: i32 = conn.del(keys.missing(key)).await?;
+    Ok(())
+}
+
+/// Store a merged PUT body as dirty if the version is still `expected`,
+/// and append its write event to `stream` in the same script.
+///
+/// Cluster: the stream lives in another slot, so `stream` is ignored and
+/// the caller appends the event itself.
+pub(crate) async fn mark_dirty(
+    conn: &mut CacheConn,
+    keys: &KeySpace,
+    key: &str,
+    value: &[u8],
+    expected: &str,
+    stream: Option<&str>,
+) -> RedisResult<MarkDirty> {
+    let script = Script::new(MARK_DIRTY_SCRIPT);
+    let mut invocation = script.prepare_invoke();
+    invocation
+        .key(keys.delete(key))
+        .key(keys.dirty(key))
+        .key(keys.clean(key))
+        .key(keys.version(key))
+        .arg(value)
+        .arg(expected)
+        .arg(key);
+    if let Some(stream) = stream.filter(|_| !keys.hash_tags) {
+        invocation.key(stream);
+    }
+    let version: i64 = invocation.invoke_async(conn).await?;
+    Ok(match version {
+        -2 => MarkDirty::Deleted,
+        -1 => MarkDirty::Conflict,
+        _ => MarkDirty::Done,
+    })
+}
+
+/// Cache a merged PUT body written through to DB, if the version is still `expected`.
+/// With `ttl` `None` ("don't cache") the entity is left without a clean entry.
+pub(crate) async fn commit(
+    conn: &mut CacheConn,
+    keys: &KeySpace,
+    key: &str,
+    value: &[u8],
+    expected: &str,
+    ttl: Option<CleanTtl>,
+) -> RedisResult<MarkDirty> {
+    let ttl = ttl.unwrap_or(CleanTtl { fresh: 0, expiry: 0 });
+    let version: i64 = Script::new(COMMIT_SCRIPT)
+        .key(keys.delete(key))
+        .key(keys.dirty(key))
+        .key(keys.clean(key))
+        .key(keys.version(key))
+        .key(keys.ttl(key))
+        .arg(value)
+        .arg(expected)
+        .arg(ttl.expiry)
+        .arg(ttl.fresh)
+        .invoke_async(conn)
+        .await?;
+    Ok(match version {
+        -2 => MarkDirty::Deleted,
+        -1 => MarkDirty::Conflict,
+        _ => MarkDirty::Done,
+    })
+}
+
+/// Drop every cached state of `key`, set the delete marker and queue
+/// the DB delete of `id` for `due` (ms since the Unix epoch).
+/// The marker has no TTL: the delete poller removes it once the DB delete is acknowledged.
+pub(crate) async fn mark_deleted(
+    conn: &mut CacheConn,
+    keys: &KeySpace,
+    root: &str,
+    key: &str,
+    id: &str,
+    due: u64,
+) -> RedisResult<()> {
+    let queue = keys.delete_queue(root);
+    let script = Script::new(MARK_DELETED_SCRIPT);
+    let mut invocation = script.prepare_invoke();
+    invocation
+        .key(keys.delete(key))
+        .key(keys.dirty(key))
+        .key(keys.clean(key))
+        .key(keys.version(key))
+        .arg(id)
+        .arg(due);
+    // Cluster: the queue lives in another slot. Queue first, so a failed
+    // script (a 500 the client retries) never loses the DB delete.
+    if keys.hash_tags {
+        let _: i32 = conn.zadd(&queue, id, due).await?;
+    } else {
+        invocation.key(&queue);
+    }
+    let _: i32 = invocation.invoke_async(conn).await?;
+    Ok(())
+}
+
+/// Drop the delete marker of `key` once its DB delete is acknowledged.
+/// The version expires after `ttl_sec`.
+pub(crate) async fn forget_deleted(conn: &mut CacheConn, keys: &KeySpace, key: &str, ttl_sec: u64) -> RedisResult<()> {
+    let _: i32 = Script::new(FORGET_DELETED_SCRIPT)
+        .key(keys.delete(key))
+        .key(keys.version(key))
+        .arg(ttl_sec)
+        .invoke_async(conn)
+        .await?;
+    Ok(())
+}
+
+/// Turn a flushed dirty entry clean if it is still at `version`, or just
+/// drop it with `ttl` `None` ("don't cache").
+/// Returns `false` if it changed or was deleted during the flush.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn flush(
+    conn: &mut CacheConn,
+    dirty_key: &str,
+    clean_key: &str,
+    version_key: &str,
+    delete_key: &str,
+    ttl_key: &str,
+    value: &str,
+    version: &str,
+    ttl: Option<CleanTtl>,
+) -> RedisResult<bool> {
+    let ttl = ttl.unwrap_or(CleanTtl { fresh: 0, expiry: 0 });
+    Script::new(FLUSH_SCRIPT)
+        .key(dirty_key)
+        .key(clean_key)
+        .key(version_key)
+        .key(delete_key)
+        .key(ttl_key)
+        .arg(value)
+        .arg(ttl.expiry)
+        .arg(version)
+        .arg(ttl.fresh)
+        .invoke_async(conn)
+        .await
+}
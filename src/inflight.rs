@@ -0,0 +1,45 @@
+// src/inflight.rs
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+type Flights = Arc<Mutex<HashMap<String, watch::Receiver<()>>>>;
+
+/// Keys with work running in this process (refresh-ahead, single-flight
+/// misses). Others can wait until that work is done.
+#[derive(Clone, Default)]
+pub(crate) struct InFlight {
+    keys: Flights,
+}
+
+/// Marks its key as in flight until dropped, which wakes every waiter.
+pub(crate) struct InFlightGuard {
+    keys: Flights,
+    key: String,
+    _done: watch::Sender<()>,
+}
+
+impl InFlight {
+    /// Claim `key`, or get a receiver that closes once the current
+    /// holder is done.
+    pub(crate) fn start(&self, key: &str) -> Result<InFlightGuard, watch::Receiver<()>> {
+        let mut keys = self.keys.lock().unwrap();
+        if let Some(done) = keys.get(key) {
+            return Err(done.clone());
+        }
+        let (done, waiter) = watch::channel(());
+        keys.insert(key.to_string(), waiter);
+        Ok(InFlightGuard {
+            keys: Arc::clone(&self.keys),
+            key: key.to_string(),
+            _done: done,
+        })
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.keys.lock().unwrap().remove(&self.key);
+    }
+}
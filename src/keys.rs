@@ -0,0 +1,196 @@
+// src/keys.rs
+
+/// Redis key layout shared by the middleware and the background workers.
+///
+/// Every key the crate touches is built here, so the global prefix and the
+/// internal marker names only have to be configured once.
+/// - clean entry: `{prefix}{key}`
+/// - dirty entry: `{prefix}{dirty}:{key}`, version `{prefix}{dirty}-version:{key}`
+/// - delete marker: `{prefix}{delete}:{key}`
+/// - single-flight miss lock: `{prefix}{lock}:{key}`
+/// - negative cache (404) marker: `{prefix}{missing}:{key}`
+/// - TTLs a clean entry was stored with: `{prefix}{ttl}:{key}`
+/// - write event stream: `{prefix}{dirty}-stream:{root}`
+/// - pending delete queue: `{prefix}{delete}-queue:{root}`
+/// - due delete stream: `{prefix}{delete}-stream:{root}`
+/// - worker leader lease: `{prefix}{leader}:{root}`, `{prefix}{leader}-token:{root}`
+/// - L1 invalidation channel: `{prefix}{invalidation}`
+///
+/// With `hash_tags` (Redis Cluster), the entity key is wrapped in `{...}`
+/// so its clean, dirty and delete keys share one slot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySpace {
+    pub prefix: String,
+    pub dirty: String,
+    pub delete: String,
+    pub lock: String,
+    pub missing: String,
+    pub ttl: String,
+    pub leader: String,
+    pub invalidation: String,
+    pub hash_tags: bool,
+}
+
+impl Default for KeySpace {
+    fn default() -> Self {
+        KeySpace {
+            prefix: String::new(),
+            dirty: "dirty".to_string(),
+            delete: "delete".to_string(),
+            lock: "lock".to_string(),
+            missing: "missing".to_string(),
+            ttl: "ttl".to_string(),
+            leader: "leader".to_string(),
+            invalidation: "l1-invalidate".to_string(),
+            hash_tags: false,
+        }
+    }
+}
+
+impl KeySpace {
+    /// Clean (flushed) cache key.
+    pub fn clean(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, self.entity(key))
+    }
+
+    /// Dirty (not yet flushed) cache key.
+    pub fn dirty(&self, key: &str) -> String {
+        format!("{}{}:{}", self.prefix, self.dirty, self.entity(key))
+    }
+
+    /// Version counter of the dirty entry, bumped on every PUT.
+    pub fn version(&self, key: &str) -> String {
+        format!("{}{}-version:{}", self.prefix, self.dirty, self.entity(key))
+    }
+
+    /// Deferred delete marker key.
+    pub fn delete(&self, key: &str) -> String {
+        format!("{}{}:{}", self.prefix, self.delete, self.entity(key))
+    }
+
+    /// Negative cache marker of an entity the handler answered 404 for.
+    pub fn missing(&self, key: &str) -> String {
+        format!("{}{}:{}", self.prefix, self.missing, self.entity(key))
+    }
+
+    /// Fresh and total TTL a clean entry was stored with, expiring with it.
+    pub fn ttl(&self, key: &str) -> String {
+        format!("{}{}:{}", self.prefix, self.ttl, self.entity(key))
+    }
+
+    /// Distributed single-flight lock of a cache miss.
+    pub fn lock(&self, key: &str) -> String {
+        format!("{}{}:{}", self.prefix, self.lock, self.entity(key))
+    }
+
+    /// `KEYS` pattern matching every dirty entry under `root`.
+    pub fn dirty_pattern(&self, root: &str) -> String {
+        format!("{}*", self.dirty_root(root))
+    }
+
+    /// `KEYS` pattern matching every delete marker under `root`.
+    pub fn delete_pattern(&self, root: &str) -> String {
+        format!("{}*", self.delete_root(root))
+    }
+
+    /// Sorted set of pending deletes under `root`, scored by due time
+    /// (ex: "delete-queue:posts").
+    pub fn delete_queue(&self, root: &str) -> String {
+        format!("{}{}-queue:{}", self.prefix, self.delete, self.entity(root))
+    }
+
+    /// Stream of due deletes under `root`, read by a consumer group
+    /// (ex: "delete-stream:posts").
+    pub fn delete_stream(&self, root: &str) -> String {
+        format!("{}{}-stream:{}", self.prefix, self.delete, self.entity(root))
+    }
+
+    /// Stream of PUT events under `root` for stream write-behind
+    /// (ex: "dirty-stream:posts").
+    pub fn dirty_stream(&self, root: &str) -> String {
+        format!("{}{}-stream:{}", self.prefix, self.dirty, self.entity(root))
+    }
+
+    /// Leader lease of the background workers for `root` (ex: "leader:posts").
+    pub fn leader(&self, root: &str) -> String {
+        format!("{}{}:{}", self.prefix, self.leader, self.entity(root))
+    }
+
+    /// Fencing token counter of the leader lease for `root`.
+    pub fn leader_token(&self, root: &str) -> String {
+        format!("{}{}-token:{}", self.prefix, self.leader, self.entity(root))
+    }
+
+    /// Pub/sub channel of in-process (L1) cache invalidations,
+    /// carrying entity keys (ex: "posts:1").
+    pub fn invalidation_channel(&self) -> String {
+        format!("{}{}", self.prefix, self.invalidation)
+    }
+
+    /// Dirty key prefix for `root` (ex: "dirty:posts:").
+    pub fn dirty_root(&self, root: &str) -> String {
+        format!("{}{}:{}{}:", self.prefix, self.dirty, self.tag_open(), root)
+    }
+
+    /// Delete marker prefix for `root` (ex: "delete:posts:").
+    pub fn delete_root(&self, root: &str) -> String {
+        format!("{}{}:{}{}:", self.prefix, self.delete, self.tag_open(), root)
+    }
+
+    /// Map a dirty key back to its clean key.
+    pub fn clean_from_dirty(&self, dirty_key: &str) -> Option<String> {
+        let marker = format!("{}{}:", self.prefix, self.dirty);
+        dirty_key
+            .strip_prefix(&marker)
+            .map(|entity| format!("{}{}", self.prefix, entity))
+    }
+
+    /// Map a dirty key to its version counter key.
+    pub fn version_from_dirty(&self, dirty_key: &str) -> Option<String> {
+        let marker = format!("{}{}:", self.prefix, self.dirty);
+        dirty_key
+            .strip_prefix(&marker)
+            .map(|entity| format!("{}{}-version:{}", self.prefix, self.dirty, entity))
+    }
+
+    /// Entity key of a dirty key (ex: "dirty:posts:1" => "posts:1").
+    pub fn key_from_dirty(&self, dirty_key: &str) -> Option<String> {
+        let marker = format!("{}{}:", self.prefix, self.dirty);
+        let entity = dirty_key.strip_prefix(&marker)?;
+        if self.hash_tags {
+            entity.strip_prefix('{')?.strip_suffix('}').map(str::to_string)
+        } else {
+            Some(entity.to_string())
+        }
+    }
+
+    /// Map a dirty key to its delete marker key.
+    pub fn delete_from_dirty(&self, dirty_key: &str) -> Option<String> {
+        let marker = format!("{}{}:", self.prefix, self.dirty);
+        dirty_key
+            .strip_prefix(&marker)
+            .map(|entity| format!("{}{}:{}", self.prefix, self.delete, entity))
+    }
+
+    /// Entity id of a delete marker under `root` (ex: "delete:posts:1" => "1").
+    pub fn delete_id<'a>(&self, root: &str, delete_key: &'a str) -> Option<&'a str> {
+        let rest = delete_key.strip_prefix(&self.delete_root(root))?;
+        if self.hash_tags {
+            rest.strip_suffix('}')
+        } else {
+            Some(rest)
+        }
+    }
+
+    fn entity(&self, key: &str) -> String {
+        if self.hash_tags {
+            format!("{{{}}}", key)
+        } else {
+            key.to_string()
+        }
+    }
+
+    fn tag_open(&self) -> &'static str {
+        if self.hash_tags { "{" } else { "" }
+    }
+}
@@ -0,0 +1,174 @@
+// src/l1.rs
+
+use colored::*;
+use futures_util::StreamExt;
+use redis::{AsyncCommands, RedisResult};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::cache::CacheConfig;
+use crate::connection::{self, CacheConn};
+use crate::keys::KeySpace;
+
+/// Delay before resubscribing after the invalidation subscription is lost.
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+/// In-process copy of fresh clean entries, in front of Redis.
+///
+/// Bounded by `CacheConfig::l1_capacity` (oldest entry out first) and
+/// `l1_ttl`. Dirty entries never get in, so a PUT is read back from Redis.
+#[derive(Clone, Default)]
+pub(crate) struct L1 {
+    entries: Arc<Mutex<L1Entries>>,
+}
+
+#[derive(Default)]
+struct L1Entries {
+    bodies: HashMap<String, (String, Instant)>,
+    // Insertion order, for eviction
+    order: VecDeque<String>,
+    // Bumped on every invalidation
+    epoch: u64,
+}
+
+impl L1 {
+    /// Body of `key` if present and not expired.
+    pub(crate) fn get(&self, key: &str) -> Option<String> {
+        let mut entries = self.entries.lock().unwrap();
+        let (body, expires) = entries.bodies.get(key)?;
+        if *expires > Instant::now() {
+            return Some(body.clone());
+        }
+        entries.remove(key);
+        None
+    }
+
+    /// Invalidation count so far. A read started at `epoch` may only be
+    /// stored if nothing was invalidated meanwhile.
+    pub(crate) fn epoch(&self) -> u64 {
+        self.entries.lock().unwrap().epoch
+    }
+
+    /// Store `body` for `ttl`, unless an invalidation came in since `epoch`.
+    pub(crate) fn insert(&self, capacity: usize, epoch: u64, key: &str, body: &str, ttl: Duration) {
+        let mut entries = self.entries.lock().unwrap();
+        if capacity == 0 || ttl.is_zero() || entries.epoch != epoch {
+            return;
+        }
+        let expires = Instant::now() + ttl;
+        if let Some(entry) = entries.bodies.get_mut(key) {
+            *entry = (body.to_string(), expires);
+            return;
+        }
+        while entries.bodies.len() >= capacity {
+            let Some(oldest) = entries.order.pop_front() else { break };
+            entries.bodies.remove(&oldest);
+        }
+        entries.bodies.insert(key.to_string(), (body.to_string(), expires));
+        entries.order.push_back(key.to_string());
+    }
+
+    /// Drop `key` from this process only.
+    pub(crate) fn remove(&self, key: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.epoch += 1;
+        entries.remove(key);
+    }
+
+    /// Drop every entry (invalidations may have been missed).
+    fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.epoch += 1;
+        entries.bodies.clear();
+        entries.order.clear();
+    }
+}
+
+impl L1Entries {
+    fn remove(&mut self, key: &str) {
+        if self.bodies.remove(key).is_some() {
+            self.order.retain(|k| k != key);
+        }
+    }
+}
+
+/// Drop `key` from L1 here and, through the invalidation channel, on
+/// every replica. No-op while L1 is off.
+pub(crate) async fn invalidate(conn: &mut CacheConn, keys: &KeySpace, l1: &L1, config: &CacheConfig, key: &str) {
+    if config.l1_capacity == 0 {
+        return;
+    }
+    l1.remove(key);
+    let published: RedisResult<i64> = conn.publish(keys.invalidation_channel(), key).await;
+    if let Err(e) = published {
+        eprintln!("❌ Failed to publish L1 invalidation of {key}: {e}");
+    }
+}
+
+/// Background task: drops L1 entries invalidated by any replica.
+///
+/// `l1s` pairs each resource root with its L1. In cluster mode every
+/// master delivers the message, so an entry may be dropped twice.
+/// When the subscription is lost, every L1 is cleared before resubscribing.
+pub(crate) async fn invalidation_listener(
+    client: redis::Client,
+    mut conn: CacheConn,
+    keys: KeySpace,
+    l1s: Vec<(String, L1)>,
+    token: CancellationToken,
+) {
+    let channel = keys.invalidation_channel();
+    println!("{} L1 invalidation listening", "Start".green().bold());
+    loop {
+        let subscribed = tokio::select! {
+            result = connection::subscribe_all(&client, &mut conn, &channel) => result,
+            _ = token.cancelled() => break,
+        };
+        let pubsubs = match subscribed {
+            Ok(pubsubs) => pubsubs,
+            Err(e) => {
+                eprintln!("❌ Failed to subscribe to L1 invalidations: {e}");
+                tokio::select! {
+                    _ = tokio::time::sleep(RESUBSCRIBE_DELAY) => continue,
+                    _ = token.cancelled() => break,
+                }
+            }
+        };
+        let mut messages = connection::merge_messages(pubsubs);
+
+        let cancelled = loop {
+            tokio::select! {
+                msg = messages.next() => {
+                    let Some(msg) = msg else { break false };
+                    let Ok(key) = msg.get_payload::<String>() else { continue };
+                    for (root, l1) in &l1s {
+                        if key.strip_prefix(root.as_str()).is_some_and(|rest| rest.starts_with(':')) {
+                            l1.remove(&key);
+                        }
+                    }
+                }
+                _ = token.cancelled() => break true,
+            }
+        };
+        if cancelled {
+            break;
+        }
+
+        for (_, l1) in &l1s {
+            l1.clear();
+        }
+        let count = conn.count_resubscribe();
+        eprintln!("⚠️ L1 invalidation subscription lost, resubscribing... (resubscribe #{count})");
+        tokio::select! {
+            _ = tokio::time::sleep(RESUBSCRIBE_DELAY) => {}
+            _ = token.cancelled() => break,
+        }
+        if let Err(e) = conn.refresh().await {
+            eprintln!("❌ Failed to reconnect to Redis: {e}");
+        }
+    }
+
+    println!("{} L1 invalidation listener shutting down...", "Shutdown".red().bold());
+}
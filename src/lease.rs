@@ -0,0 +1,169 @@
+// src/lease.rs
+
+use colored::*;
+use redis::Script;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+use crate::cache_sync::now_millis;
+use crate::connection::CacheConn;
+use crate::keys::KeySpace;
+
+/// Leader lease lifetime. A crashed leader is replaced after at most this long.
+const LEASE_TTL: Duration = Duration::from_secs(10);
+
+/// Held leases are renewed when older than this.
+const LEASE_RENEW: Duration = Duration::from_secs(3);
+
+/// Acquire or renew the lease. A new holder gets the next fencing token.
+/// Returns the token, or nil if another replica holds the lease.
+const ACQUIRE_SCRIPT: &str = r#"
+local lock_key = KEYS[1]
+local token_key = KEYS[2]
+local owner = ARGV[1]
+local ttl_ms = ARGV[2]
+local current = redis.call('get', lock_key)
+if current then
+    local holder, token = string.match(current, '^(.-):(%d+)$')
+    if holder == owner then
+        redis.call('pexpire', lock_key, ttl_ms)
+        return tonumber(token)
+    end
+    return false
+end
+local token = redis.call('incr', token_key)
+redis.call('set', lock_key, owner .. ':' .. token, 'PX', ttl_ms)
+return token
+"#;
+
+/// Delete the lease only if it still carries our fence.
+const RELEASE_SCRIPT: &str = r#"
+if redis.call('get', KEYS[1]) == ARGV[1] then
+    return redis.call('del', KEYS[1])
+end
+return 0
+"#;
+
+/// Id unique to one worker set, also within a process
+/// (lease owner, stream consumer name).
+pub(crate) fn instance_id() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let seq = NEXT.fetch_add(1, Ordering::Relaxed);
+    format!("{}-{}-{}", std::process::id(), now_millis(), seq)
+}
+
+/// Per-resource leader leases of one set of background workers.
+///
+/// Every replica runs the workers, but only the lease holder of a resource
+/// flushes its dirty entries and moves its due deletes. The lease value is
+/// `{owner}:{token}`; the token grows on every change of holder, so Redis
+/// scripts can reject work from a holder whose lease already moved on.
+pub(crate) struct Leases {
+    owner: String,
+    leases: Vec<Lease>,
+}
+
+struct Lease {
+    root_key: String,
+    lock_key: String,
+    token_key: String,
+    // fencing token + time of the last successful renew
+    held: Mutex<Option<(u64, Instant)>>,
+}
+
+impl Leases {
+    pub(crate) fn new<'a>(keys: &KeySpace, roots: impl IntoIterator<Item = &'a str>) -> Self {
+        let leases = roots
+            .into_iter()
+            .map(|root| Lease {
+                root_key: root.to_string(),
+                lock_key: keys.leader(root),
+                token_key: keys.leader_token(root),
+                held: Mutex::new(None),
+            })
+            .collect();
+        Leases {
+            owner: instance_id(),
+            leases,
+        }
+    }
+
+    /// Lock key of resource `i`.
+    pub(crate) fn lock_key(&self, i: usize) -> &str {
+        &self.leases[i].lock_key
+    }
+
+    /// Fence value (`{owner}:{token}`) if this replica leads resource `i`.
+    /// Acquires or renews the lease when due.
+    pub(crate) async fn fence(&self, i: usize, conn: &mut CacheConn) -> Option<String> {
+        let lease = &self.leases[i];
+        let mut held = lease.held.lock().await;
+        if let Some((token, renewed)) = *held
+            && renewed.elapsed() < LEASE_RENEW
+        {
+            return Some(self.fence_value(token));
+        }
+
+        let result: redis::RedisResult<Option<u64>> = Script::new(ACQUIRE_SCRIPT)
+            .key(&lease.lock_key)
+            .key(&lease.token_key)
+            .arg(&self.owner)
+            .arg(LEASE_TTL.as_millis() as u64)
+            .invoke_async(conn)
+            .await;
+        match result {
+            Ok(Some(token)) => {
+                if held.map(|(held, _)| held) != Some(token) {
+                    println!(
+                        "{} Cache leader for {} (fencing token {token})",
+                        "Lease".cyan().bold(),
+                        lease.root_key
+                    );
+                }
+                *held = Some((token, Instant::now()));
+                Some(self.fence_value(token))
+            }
+            Ok(None) => {
+                if held.take().is_some() {
+                    eprintln!("⚠️ Lost cache leadership for {}", lease.root_key);
+                }
+                None
+            }
+            Err(e) => {
+                eprintln!("❌ Failed to renew leader lease: {e}");
+                // Still ours until the lease can have expired on the server
+                match *held {
+                    Some((token, renewed)) if renewed.elapsed() < LEASE_TTL - LEASE_RENEW => {
+                        Some(self.fence_value(token))
+                    }
+                    _ => {
+                        *held = None;
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// Give up every held lease so another replica takes over right away.
+    pub(crate) async fn release_all(&self, conn: &mut CacheConn) {
+        for lease in &self.leases {
+            let Some((token, _)) = lease.held.lock().await.take() else {
+                continue;
+            };
+            let result: redis::RedisResult<i32> = Script::new(RELEASE_SCRIPT)
+                .key(&lease.lock_key)
+                .arg(self.fence_value(token))
+                .invoke_async(conn)
+                .await;
+            if let Err(e) = result {
+                eprintln!("❌ Failed to release leader lease: {e}");
+            }
+        }
+    }
+
+    fn fence_value(&self, token: u64) -> String {
+        format!("{}:{}", self.owner, token)
+    }
+}
@@ -96,6 +96,25 @@
 mod cache;
 mod middleware;
 mod cache_sync;
+mod keys;
+mod registry;
+mod connection;
+mod entity;
+mod inflight;
+mod l1;
+mod lease;
+mod single_flight;
+mod streams;
+mod ttl;
+#[cfg(feature = "tls")]
+mod tls;
 
 pub use cache::*;
-pub use middleware::*;
\ No newline at end of file
+pub use middleware::*;
+pub use keys::*;
+pub use registry::*;
+pub use cache_sync::CallbackOutcome;
+pub use connection::CacheConn;
+pub use ttl::{CacheTtl, ResponseTtl, TtlContext, TtlPolicy, CACHE_TTL_HEADER, MAX_TTL};
+#[cfg(feature = "tls")]
+pub use tls::TlsConfig;
\ No newline at end of file
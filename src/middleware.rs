@@ -2,28 +2,56 @@
 
 use axum::{
     extract::State,
-    http::{Request, Response, StatusCode},
+    http::{header::{CONTENT_LENGTH, LOCATION}, HeaderMap, Request, Response, StatusCode, Uri},
     middleware::Next,
 };
-use redis::{AsyncCommands, RedisResult, aio::MultiplexedConnection};
+use futures_util::StreamExt;
+use redis::{AsyncCommands, RedisResult};
 use axum::http::Method;
 use http_body_util::BodyExt;
 use bytes::Bytes;
 use axum::body::Body;
 
 use crate::cache;
+use crate::cache_sync;
+use crate::connection::CacheConn;
+use crate::entity::{self, CleanTtl, Entry, MarkDirty, Snapshot};
+use crate::l1;
+use crate::single_flight;
+use crate::ttl::{TtlContext, MAX_TTL};
 
 /// Main middleware for cache handling.
 /// You can use other cache middleware functions, and not use this one.
 ///
 /// Handles GET, PUT, DELETE logic with Redis backend.
 /// - Returns cached data if present
-/// - Marks as dirty on PUT
-/// - deferred delete via `delete:` key on DELETE
+/// - Marks as dirty on PUT, or writes to DB first with `WriteMode::WriteThrough`
+/// - deferred delete via `delete:` key and the pending delete queue on DELETE
+/// - remembers GET 404s under `missing:` with `CacheConfig::with_not_found_ttl`,
+///   dropped by writes on the id or a create naming it in `Location`
+/// - serves fresh clean entries from process memory with `CacheConfig::with_l1`
+/// - streams responses over `CacheConfig::with_max_body_size` through uncached,
+///   and answers PUT bodies over `with_max_request_size` with 413
+///
+/// Every state change of an entity is one Lua script (see the state
+/// machine in `entity`), so concurrent requests and the background
+/// workers never interleave halfway.
+///
+/// Paths outside the state's `root_key` are passed through untouched,
+/// since no background worker would ever flush or delete them.
 pub async fn middleware(
     State(state): State<cache::CacheState>,
     req: Request<Body>,
     next: Next,
+) -> Result<Response<Body>, StatusCode> {
+    handle_request(state, req, next).await
+}
+
+/// Cache logic behind `middleware`, shared with `registry_middleware`.
+pub(crate) async fn handle_request(
+    state: cache::CacheState,
+    req: Request<Body>,
+    next: Next,
 ) -> Result<Response<Body>, StatusCode> {
     // Extract key from path and query
     let key = req
@@ -37,62 +65,211 @@ pub async fn middleware(
     };
 
     let key = normalize_path(&key);
+    if !is_under_root(&key, &state.root_key) {
+        let response = next.run(req).await;
+        // A create on the collection (ex: POST /posts) names the new entity
+        if let Some(path) = created_path(&response) {
+            forget_created(&state, &path).await;
+        }
+        return Ok(response);
+    }
 
-    // Check for deleted marker in Redis
-    let del_key = String::from("delete:") + &key;
-    let mut conn = state.conn;
-    let write_to_cache = state.write_to_cache;
-    if conn.exists(&del_key).await.unwrap() {
-        let final_response = Response::builder()
-            .status(404)
-            .body(Body::empty());
-        match final_response {
-            Ok(resp) => return Ok(resp),
-            Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
-        };
+    let config = *state.config.lock().unwrap();
+    if *req.method() == Method::GET {
+        // In-process hit: no Redis round trip
+        if config.l1_capacity > 0 && let Some(body) = state.l1.get(&key) {
+            println!("✅ L1 cache hit: {}", key);
+            return Ok(build_cached_response(body));
+        }
+        return handle_entity(&state, key, req, next).await;
     }
+    let response = handle_entity(&state, key.clone(), req, next).await;
+    // Writes drop the entity from L1 on every replica
+    l1::invalidate(&mut state.conn.clone(), &state.keys, &state.l1, &config, &key).await;
+    response
+}
+
+/// Redis side of `handle_request` for a key under the state's root.
+async fn handle_entity(
+    state: &cache::CacheState,
+    key: String,
+    req: Request<Body>,
+    next: Next,
+) -> Result<Response<Body>, StatusCode> {
+    let keys = state.keys.clone();
+    let mut conn = state.conn.clone();
+    let write_to_cache = state.write_to_cache;
 
     // Dispatch based on HTTP method
-    match req.method() {
-        &Method::GET => {
-            // Try dirty or clean cache hit
-            if let Some(cached_body) = get_dirty_or_clean(&mut conn, &key).await? {
-                return Ok(build_cached_response(cached_body));
+    match *req.method() {
+        Method::GET => {
+            // Deleted, dirty or clean in one read
+            let epoch = state.l1.epoch();
+            let snapshot = read_entry(state, &mut conn, &key).await?;
+            match snapshot.entry {
+                Entry::Deleted => return Ok(not_found()),
+                Entry::NotFound => return Ok(cached_not_found()),
+                Entry::Dirty(cached_body) => return Ok(build_cached_response(cached_body)),
+                Entry::Clean(cached_body) => {
+                    if snapshot.clean_ttl_ms < 0 {
+                        // Loaded with a 0 TTL: served, not cached
+                        return Ok(build_cached_response(cached_body));
+                    }
+                    let config = *state.config.lock().unwrap();
+                    let stored = stored_ttls(state, &config, &key, snapshot.stored);
+                    // Time since the entry was stored, and past its fresh TTL (<= 0 while fresh)
+                    let age_ms = (stored.expiry as i64 * 1000 - snapshot.clean_ttl_ms).max(0);
+                    let stale_ms = age_ms - stored.fresh as i64 * 1000;
+                    let age = age_ms / 1000;
+                    if stale_ms <= 0 {
+                        // L1 copies never outlive the fresh TTL
+                        let l1_ttl = config.l1_ttl.min(std::time::Duration::from_millis(-stale_ms as u64));
+                        state.l1.insert(config.l1_capacity, epoch, &key, &cached_body, l1_ttl);
+                        if needs_refresh(&config, stored, snapshot.clean_ttl_ms) {
+                            spawn_refresh(state, &key, snapshot.version, &req, next);
+                        }
+                        return Ok(build_cached_response(cached_body));
+                    }
+                    if stale_ms <= config.stale_while_revalidate as i64 * 1000 {
+                        spawn_refresh(state, &key, snapshot.version, &req, next);
+                        return Ok(build_stale_response(cached_body, age, STALE_WARNING));
+                    }
+
+                    // Past the revalidation grace: handler first, stale copy if it fails
+                    let handler = fill_from_handler(state, &mut conn, &key, &snapshot.version, req, next);
+                    let result = match config.handler_timeout {
+                        Some(timeout) => tokio::time::timeout(timeout, handler).await.ok(),
+                        None => Some(handler.await),
+                    };
+                    return match result {
+                        Some(Ok(response)) if !response.status().is_server_error() => Ok(response),
+                        _ => {
+                            println!("⚠️ Revalidation of {key} failed, serving stale copy");
+                            Ok(build_stale_response(cached_body, age, REVALIDATION_FAILED_WARNING))
+                        }
+                    };
+                }
+                // Continue if cache miss
+                Entry::Miss => {}
             }
-            // Continue if cache miss
+            // Coalesce with concurrent misses on this key
+            let (snapshot, leader) = single_flight::join(state, &mut conn, &key, snapshot)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            match snapshot.entry {
+                Entry::Deleted => return Ok(not_found()),
+                Entry::NotFound => return Ok(cached_not_found()),
+                Entry::Dirty(cached_body) | Entry::Clean(cached_body) => {
+                    return Ok(build_cached_response(cached_body));
+                }
+                Entry::Miss => {}
+            }
+            let response = fill_from_handler(state, &mut conn, &key, &snapshot.version, req, next).await;
+            if let Some(leader) = leader {
+                leader.release(&mut conn).await;
+            }
+            return response;
         }
-        &Method::PUT => {
-            if let Some(cached_body) = get_dirty_or_clean(&mut conn, &key).await? {
-                let (_, body) = req.into_parts();
-                let collected = body.collect().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-                let new_body = String::from_utf8_lossy(&collected.to_bytes()).to_string();
-
-                // Call custom cache merger (usually JSON merge)
-                let response_json = write_to_cache(cached_body, new_body);
-                let response_bytes = response_json.into_bytes();
-
-                // Store as dirty, delete clean
-                let dirty_key = format!("dirty:{}", key);
-                let _: () = conn.set(&dirty_key, &response_bytes).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-                let _: RedisResult<i32> = conn.del(&key).await;
-
-                return Ok(
-                    Response::builder()
-                        .status(200)
-                        .header("X-Cache", "HIT")
-                        .header("Content-Type", "application/json")
-                        .body(Body::from(response_bytes))
-                        .unwrap(),
-                );
+        Method::PUT => {
+            let Snapshot { entry, mut version, .. } = read_entry(state, &mut conn, &key).await?;
+            let mut cached_body = match entry {
+                Entry::Deleted => return Ok(not_found()),
+                Entry::Dirty(body) | Entry::Clean(body) => Some(body),
+                Entry::NotFound => {
+                    // The PUT may create it
+                    entity::forget_missing(&mut conn, &keys, &key)
+                        .await
+                        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+                    None
+                }
+                Entry::Miss => None,
+            };
+            if cached_body.is_some() {
+                let (parts, body) = req.into_parts();
+                let max_request_size = state.config.lock().unwrap().max_request_size;
+                let collected = match collect_limited(body, &parts.headers, max_request_size).await {
+                    Ok(Collected::Buffered(bytes)) => bytes,
+                    Ok(Collected::TooLarge(_)) => return Err(StatusCode::PAYLOAD_TOO_LARGE),
+                    Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+                };
+                let new_body = String::from_utf8_lossy(&collected).to_string();
+
+                // Each conflict means another PUT got in, so this always ends
+                loop {
+                    let Some(base) = cached_body.take() else {
+                        // Flushed and expired meanwhile: let the handler take it
+                        let req = Request::from_parts(parts, Body::from(new_body));
+                        return fill_from_handler(state, &mut conn, &key, &version, req, next).await;
+                    };
+
+                    // Call custom cache merger (usually JSON merge)
+                    let response_json = write_to_cache(base, new_body.clone());
+                    let response_bytes = response_json.as_bytes();
+
+                    let config = *state.config.lock().unwrap();
+                    let write_through = config.write_mode == cache::WriteMode::WriteThrough;
+                    let stream = match config.write_behind {
+                        cache::WriteBehind::Stream { .. } => Some(keys.dirty_stream(&state.root_key)),
+                        cache::WriteBehind::Poll => None,
+                    };
+                    let outcome = if write_through {
+                        // DB first; the cache only follows a successful write
+                        if let Err(e) = (state.write_function)(response_json.clone()).await {
+                            eprintln!("❌ Failed to write {key} through to DB: {e}");
+                            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                        }
+                        let ttl = config.clean_ttls(state.clean_ttl(&config, &key, None));
+                        entity::commit(&mut conn, &keys, &key, response_bytes, &version, ttl).await
+                    } else {
+                        // Store as dirty with a new version, unless changed since read
+                        entity::mark_dirty(&mut conn, &keys, &key, response_bytes, &version, stream.as_deref()).await
+                    };
+                    match outcome.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+                        MarkDirty::Done => {}
+                        MarkDirty::Deleted => return Ok(not_found()),
+                        MarkDirty::Conflict => {
+                            // Merge again on top of the newer state (and write it
+                            // through again, the DB may hold a body without its fields)
+                            let Snapshot { entry, version: newer, .. } = read_entry(state, &mut conn, &key).await?;
+                            cached_body = match entry {
+                                Entry::Deleted => return Ok(not_found()),
+                                Entry::Dirty(body) | Entry::Clean(body) => Some(body),
+                                Entry::NotFound | Entry::Miss => None,
+                            };
+                            version = newer;
+                            continue;
+                        }
+                    }
+                    if write_through {
+                        return Ok(build_cached_response(response_json));
+                    }
+
+                    if let Some(stream) = stream.filter(|_| keys.hash_tags) {
+                        // Cluster: appended apart from the script. The PUT is already
+                        // stored, so a failure is not retried by the client (the
+                        // merge is not idempotent); the leader's sweep writes it
+                        let added: RedisResult<String> = conn.xadd(stream, "*", &[("key", &key)]).await;
+                        if let Err(e) = added {
+                            eprintln!("⚠️ Failed to queue write event of {key}, left for the dirty sweep: {e}");
+                        }
+                    }
+
+                    return Ok(build_cached_response(response_json));
+                }
             }
             // Continue if cache miss
+            return fill_from_handler(state, &mut conn, &key, &version, req, next).await;
         }
-        &Method::DELETE => {
-            // Remove both dirty/clean, mark deleted for soft delete TTL
-            let _: RedisResult<i32> = conn.del(&key).await;
-            let _: RedisResult<i32> = conn.del(&format!("dirty:{}", key)).await;
-            let ttl = state.config.lock().unwrap().ttl_deleted;
-            let _: RedisResult<()> = conn.set_ex(&format!("delete:{}", key), "1", ttl).await;
+        Method::DELETE => {
+            // Remove dirty/clean, mark deleted until the DB delete, queued after the soft delete TTL, is done
+            let config = *state.config.lock().unwrap();
+            let ttl = config.jittered(state.ttl_policy.deleted(&config, &TtlContext::key(&state.root_key, &key)));
+            if let Some(id) = entity_id(&state.root_key, &key) {
+                let due = cache_sync::now_millis() + ttl * 1000;
+                entity::mark_deleted(&mut conn, &keys, &state.root_key, &key, id, due)
+                    .await
+                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            }
 
             return Ok(
                 Response::builder()
@@ -101,71 +278,293 @@ pub async fn middleware(
                     .unwrap(),
             );
         }
-        _ => (),
+        // Other methods (ex: POST) may create the entity
+        _ => {
+            entity::forget_missing(&mut conn, &keys, &key)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
     }
 
-    let method = req.method().clone();
+    Ok(next.run(req).await)
+}
+
+/// Forward a cache miss to the real handler and cache a 200 result,
+/// unless the entity changed since `version` was read.
+async fn fill_from_handler(
+    state: &cache::CacheState,
+    conn: &mut CacheConn,
+    key: &str,
+    version: &str,
+    req: Request<Body>,
+    next: Next,
+) -> Result<Response<Body>, StatusCode> {
+    let is_get = req.method() == Method::GET;
     // Forward to real handler if cache miss
     let response = next.run(req).await;
 
-    // After handler: Optionally cache (GET, PUT) result
-    match method {
-        Method::GET | Method::PUT => {
-            // Extract response body
-            let (parts, body) = response.into_parts();
+    // Extract response body
+    let (parts, body) = response.into_parts();
 
-            if parts.status != StatusCode::OK {
-                return Ok(Response::from_parts(parts, Body::empty()));
-            }
-            
-            let collected = body.collect().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-            let bytes: Bytes = collected.to_bytes();
-            let string_body = String::from_utf8_lossy(&bytes).to_string();
-            // Store in Redis with dynamic TTL from config
-            let ttl = state.config.lock().unwrap().ttl_clean;
-            match conn.set_ex::<_, _, ()>(key, string_body, ttl).await {
-                Ok(_) => (),
-                Err(_) => {
-                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
-                }
-            }
-            // Reassemble response
-            let final_response = Response::from_parts(parts, Body::from(bytes));
-            Ok(final_response)
+    if parts.status == StatusCode::NOT_FOUND && is_get {
+        // Negative cache, unless the entity appeared meanwhile
+        let config = *state.config.lock().unwrap();
+        let ttl = config.jittered(config.ttl_not_found);
+        if ttl > 0 && entity::mark_missing(conn, &state.keys, key, version, ttl).await.is_err() {
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
         }
-        _ => Err(StatusCode::INTERNAL_SERVER_ERROR),
     }
-}
 
-/// Try dirty cache first, then clean cache.
-///
-/// Returns: Some(body) if hit, None if miss.
-async fn get_dirty_or_clean(
-    conn: &mut MultiplexedConnection,
-    key: &str,
-) -> Result<Option<String>, StatusCode> {
-    let dirty_key = format!("dirty:{}", key);
+    if parts.status != StatusCode::OK {
+        return Ok(Response::from_parts(parts, Body::empty()));
+    }
 
-    match conn.get::<_, Option<String>>(&dirty_key).await {
-        Ok(Some(val)) => {
-            println!("✅ Redis dirty cache hit: {}", key);
-            return Ok(Some(val));
+    let config = *state.config.lock().unwrap();
+    let bytes = match collect_limited(body, &parts.headers, config.max_body_size).await {
+        Ok(Collected::Buffered(bytes)) => bytes,
+        Ok(Collected::TooLarge(body)) => {
+            println!("⚠️ Response of {key} is over max_body_size, streamed uncached");
+            return Ok(Response::from_parts(parts, body));
         }
-        Ok(None) => {}
         Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+    let string_body = String::from_utf8_lossy(&bytes).to_string();
+    // Store in Redis with the policy's TTL, unless a PUT or DELETE won the race
+    let Some(ttl) = config.clean_ttls(state.clean_ttl(&config, key, Some(&parts))) else {
+        // TTL 0: served, not cached
+        return Ok(Response::from_parts(parts, Body::from(bytes)));
+    };
+    if entity::fill(conn, &state.keys, key, &string_body, version, ttl).await.is_err() {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    // Reassemble response
+    let final_response = Response::from_parts(parts, Body::from(bytes));
+    Ok(final_response)
+}
+
+/// A body read up to a size limit.
+/// - `Buffered`: the whole body, within the limit
+/// - `TooLarge`: over the limit; what was read is replayed before the rest
+enum Collected {
+    Buffered(Bytes),
+    TooLarge(Body),
+}
+
+/// Buffer `body` unless it is larger than `limit` bytes (`None` = no limit).
+/// A `Content-Length` over the limit skips reading altogether.
+async fn collect_limited(body: Body, headers: &HeaderMap, limit: Option<usize>) -> Result<Collected, axum::Error> {
+    let Some(limit) = limit else {
+        return Ok(Collected::Buffered(body.collect().await?.to_bytes()));
+    };
+    let declared = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    if declared.is_some_and(|len| len > limit) {
+        return Ok(Collected::TooLarge(body));
     }
 
-    match conn.get::<_, Option<String>>(key).await {
-        Ok(Some(val)) => {
-            println!("✅ Redis clean cache hit: {}", key);
-            Ok(Some(val))
+    let mut body = body;
+    let mut chunks: Vec<Bytes> = Vec::new();
+    let mut size = 0;
+    while let Some(frame) = body.frame().await {
+        // Trailers are dropped, like with `collect().to_bytes()`
+        let Ok(data) = frame?.into_data() else { continue };
+        size += data.len();
+        chunks.push(data);
+        if size > limit {
+            let read = futures_util::stream::iter(chunks.into_iter().map(Ok::<_, axum::Error>));
+            return Ok(Collected::TooLarge(Body::from_stream(read.chain(body.into_data_stream()))));
         }
-        Ok(None) => {
-            println!("❌ Cache miss: {}", key);
-            Ok(None)
+    }
+    Ok(Collected::Buffered(Bytes::from(chunks.concat())))
+}
+
+/// Whether a clean entry stored with `stored` and `clean_ttl_ms` left in
+/// Redis should be refreshed: stale (past its fresh TTL), or fresh with
+/// less than `refresh_ahead` of its fresh TTL left.
+fn needs_refresh(config: &cache::CacheConfig, stored: CleanTtl, clean_ttl_ms: i64) -> bool {
+    if clean_ttl_ms < 0 {
+        return false;
+    }
+    let fresh_ms = clean_ttl_ms - stored.expiry.saturating_sub(stored.fresh) as i64 * 1000;
+    if fresh_ms <= 0 {
+        return true;
+    }
+    config
+        .refresh_ahead
+        .is_some_and(|fraction| (fresh_ms as f64) < fraction * (stored.fresh * 1000) as f64)
+}
+
+/// TTLs a clean entry was `stored` with. Entries stored before they
+/// were recorded fall back to the current policy.
+fn stored_ttls(state: &cache::CacheState, config: &cache::CacheConfig, key: &str, stored: Option<CleanTtl>) -> CleanTtl {
+    stored.unwrap_or_else(|| {
+        let fresh = state.clean_ttl(config, key, None).min(MAX_TTL);
+        CleanTtl { fresh, expiry: fresh + config.stale_grace() }
+    })
+}
+
+/// Refresh a clean entry in the background, through the loader if any,
+/// else through the handler (same URI and headers). One refresh per key at a time.
+fn spawn_refresh(
+    state: &cache::CacheState,
+    key: &str,
+    version: String,
+    req: &Request<Body>,
+    next: Next,
+) {
+    let Ok(guard) = state.refreshing.start(key) else {
+        return;
+    };
+
+    let mut refresh_req = Request::new(Body::empty());
+    *refresh_req.uri_mut() = req.uri().clone();
+    *refresh_req.headers_mut() = req.headers().clone();
+    let state = state.clone();
+    let key = key.to_string();
+    tokio::spawn(async move {
+        let _guard = guard;
+        // A refresh that just finished already reset the TTL
+        let mut conn = state.conn.clone();
+        let Ok(snapshot) = entity::read(&mut conn, &state.keys, &key).await else {
+            return;
+        };
+        let config = *state.config.lock().unwrap();
+        let stored = stored_ttls(&state, &config, &key, snapshot.stored);
+        if !matches!(snapshot.entry, Entry::Clean(_)) || !needs_refresh(&config, stored, snapshot.clean_ttl_ms) {
+            return;
         }
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        let refreshed = match &state.loader {
+            Some(loader) => match entity_id(&state.root_key, &key) {
+                Some(id) => loader(id.to_string()).await.map(|body| (body, state.clean_ttl(&config, &key, None))),
+                None => None,
+            },
+            None => {
+                let (parts, body) = next.run(refresh_req).await.into_parts();
+                match collect_limited(body, &parts.headers, config.max_body_size).await {
+                    Ok(Collected::Buffered(bytes)) if parts.status == StatusCode::OK => Some((
+                        String::from_utf8_lossy(&bytes).to_string(),
+                        state.clean_ttl(&config, &key, Some(&parts)),
+                    )),
+                    _ => None,
+                }
+            }
+        };
+        let Some((body, ttl)) = refreshed else {
+            eprintln!("⚠️ Refresh of {key} returned nothing, entry left to expire");
+            return;
+        };
+        let Some(ttl) = config.clean_ttls(ttl) else {
+            println!("⚠️ Refresh of {key} has a 0 TTL, entry left to expire");
+            return;
+        };
+        if let Err(e) = entity::fill(&mut conn, &state.keys, &key, &body, &version, ttl).await {
+            eprintln!("❌ Failed to refresh {key}: {e}");
+        }
+        // Other replicas may hold the old body in L1 too
+        l1::invalidate(&mut conn, &state.keys, &state.l1, &config, &key).await;
+    });
+}
+
+/// Read the entity state, logging hits and misses.
+async fn read_entry(
+    state: &cache::CacheState,
+    conn: &mut CacheConn,
+    key: &str,
+) -> Result<Snapshot, StatusCode> {
+    let snapshot = read_through(state, conn, key)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    match &snapshot.entry {
+        Entry::Dirty(_) => println!("✅ Redis dirty cache hit: {}", key),
+        Entry::Clean(_) => println!("✅ Redis clean cache hit: {}", key),
+        Entry::Miss => println!("❌ Cache miss: {}", key),
+        Entry::NotFound => println!("✅ Redis not found hit: {}", key),
+        Entry::Deleted => {}
     }
+    Ok(snapshot)
+}
+
+/// Read the entity state; on a miss, fill it from the state's loader if any
+/// (coalesced like handler misses).
+pub(crate) async fn read_through(
+    state: &cache::CacheState,
+    conn: &mut CacheConn,
+    key: &str,
+) -> RedisResult<Snapshot> {
+    let snapshot = entity::read(conn, &state.keys, key).await?;
+    let (Entry::Miss, Some(loader)) = (&snapshot.entry, &state.loader) else {
+        return Ok(snapshot);
+    };
+    let Some(id) = entity_id(&state.root_key, key) else {
+        return Ok(snapshot);
+    };
+    let (snapshot, leader) = single_flight::join(state, conn, key, snapshot).await?;
+    let Some(leader) = leader else {
+        return Ok(snapshot);
+    };
+    let loaded = match loader(id.to_string()).await {
+        Some(body) => {
+            println!("✅ Loaded into cache: {}", key);
+            let config = *state.config.lock().unwrap();
+            match config.clean_ttls(state.clean_ttl(&config, key, None)) {
+                Some(ttl) => entity::fill(conn, &state.keys, key, &body, &snapshot.version, ttl)
+                    .await
+                    .map(|_| Snapshot {
+                        entry: Entry::Clean(body),
+                        clean_ttl_ms: (ttl.expiry * 1000) as i64,
+                        stored: Some(ttl),
+                        ..snapshot
+                    }),
+                // TTL 0: served, not cached
+                None => Ok(Snapshot { entry: Entry::Clean(body), clean_ttl_ms: -2, stored: None, ..snapshot }),
+            }
+        }
+        None => Ok(snapshot),
+    };
+    leader.release(conn).await;
+    loaded
+}
+
+/// Entity id of `key` under `root` (ex: "posts:1" => "1").
+fn entity_id<'a>(root: &str, key: &'a str) -> Option<&'a str> {
+    key.strip_prefix(root).and_then(|rest| rest.strip_prefix(':'))
+}
+
+/// Empty 404 served from the negative cache.
+fn cached_not_found() -> Response<Body> {
+    Response::builder()
+        .status(404)
+        .header("X-Cache", "HIT")
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// Empty 404 for entities pending deletion.
+fn not_found() -> Response<Body> {
+    Response::builder()
+        .status(404)
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// `Warning` of a stale copy served during revalidation.
+const STALE_WARNING: &str = "110 - \"Response is Stale\"";
+
+/// `Warning` of a stale copy served because revalidation failed.
+const REVALIDATION_FAILED_WARNING: &str = "111 - \"Revalidation Failed\"";
+
+/// Build an Axum Response from a stale cached copy, `age` seconds old.
+fn build_stale_response(body: String, age: i64, warning: &str) -> Response<Body> {
+    Response::builder()
+        .status(200)
+        .header("X-Cache", "STALE")
+        .header("Age", age)
+        .header("Warning", warning)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body))
+        .unwrap()
 }
 
 /// Build an Axum Response from cached data.
@@ -178,6 +577,33 @@ fn build_cached_response(body: String) -> Response<Body> {
         .unwrap()
 }
 
+/// Path of the entity a 2xx response says it created (`Location` header).
+pub(crate) fn created_path(response: &Response<Body>) -> Option<String> {
+    if !response.status().is_success() {
+        return None;
+    }
+    let location = response.headers().get(LOCATION)?.to_str().ok()?;
+    let uri = location.parse::<Uri>().ok()?;
+    Some(uri.path().to_string())
+}
+
+/// Drop the 404 marker of the entity at `path` if it is under the state's root.
+pub(crate) async fn forget_created(state: &cache::CacheState, path: &str) {
+    let key = normalize_path(path);
+    if !is_under_root(&key, &state.root_key) {
+        return;
+    }
+    if let Err(e) = entity::forget_missing(&mut state.conn.clone(), &state.keys, &key).await {
+        eprintln!("❌ Failed to drop the 404 marker of {key}: {e}");
+    }
+}
+
+/// Check whether a normalized key belongs to `root` (ex: "posts:1" under "posts").
+fn is_under_root(key: &str, root: &str) -> bool {
+    key.strip_prefix(root)
+        .is_some_and(|rest| rest.starts_with(':'))
+}
+
 /// Normalize path to redis key (ex: "/foo/bar" => "foo:bar")
 fn normalize_path(path: &str) -> String {
     let trimmed = path.strip_prefix('/').unwrap_or(path);
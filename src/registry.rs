@@ -0,0 +1,330 @@
+// src/registry.rs
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{Request, Response, StatusCode},
+    middleware::Next,
+};
+use colored::*;
+use sqlx::{Database, Pool};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use crate::cache::{CacheConfig, CacheState, EvictionCallback, Workers};
+use crate::inflight::InFlight;
+use crate::l1::L1;
+use crate::ttl::{SharedTtlPolicy, TtlPolicy};
+use crate::cache_sync::{self, CallbackOutcome, DbCallback, DbLoader, SyncResource};
+use crate::connection::CacheConn;
+use crate::keys::KeySpace;
+use crate::middleware::{created_path, forget_created, handle_request};
+
+/// One cached resource for `CacheRegistry`.
+/// - `key`: root key (ex: "posts")
+/// - `route`: axum route pattern (ex: "/posts/:id")
+pub struct CacheResource<DB: Database> {
+    key: String,
+    route: String,
+    put_function: DbCallback<DB>,
+    delete_function: DbCallback<DB>,
+    put_cache_function: fn(String, String) -> String,
+    loader: Option<DbLoader<DB>>,
+    ttl_policy: SharedTtlPolicy,
+    config: CacheConfig,
+}
+
+impl<DB: Database> CacheResource<DB> {
+    /// Describe a resource with its callbacks.
+    ///
+    /// - `put_function`: DB writer for write-behind
+    /// - `delete_function`: DB remover for delete events
+    /// - `put_cache_function`: Cache body merger for PUT
+    pub fn new<F, G, Fut1, Fut2>(
+        key: &str,
+        route: &str,
+        put_function: F,
+        delete_function: G,
+        put_cache_function: fn(String, String) -> String,
+    ) -> Self
+    where
+        F: Fn(Pool<DB>, String) -> Fut1 + Send + Sync + 'static,
+        G: Fn(Pool<DB>, String) -> Fut2 + Send + Sync + 'static,
+        Fut1: Future + Send + 'static,
+        Fut1::Output: CallbackOutcome,
+        Fut2: Future + Send + 'static,
+        Fut2::Output: CallbackOutcome,
+    {
+        CacheResource {
+            key: key.to_string(),
+            route: route.to_string(),
+            put_function: cache_sync::boxed_callback(put_function),
+            delete_function: cache_sync::boxed_callback(delete_function),
+            put_cache_function,
+            loader: None,
+            ttl_policy: SharedTtlPolicy::default(),
+            config: CacheConfig::default(),
+        }
+    }
+
+    /// Pick TTLs per entry of this resource. See `CacheManager::with_ttl_policy`.
+    pub fn with_ttl_policy(self, policy: impl TtlPolicy + 'static) -> Self {
+        self.ttl_policy.set(Arc::new(policy));
+        self
+    }
+
+    /// Fill cache misses of this resource from `loader` (`Pool<DB>`, id).
+    /// See `CacheManager::with_loader`.
+    pub fn with_loader<L, Fut>(mut self, loader: L) -> Self
+    where
+        L: Fn(Pool<DB>, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Option<String>> + Send + 'static,
+    {
+        self.loader = Some(cache_sync::boxed_loader(loader));
+        self
+    }
+
+    /// Set the cache configuration of this resource.
+    pub fn with_config(mut self, config: CacheConfig) -> Self {
+        self.config = config;
+        self
+    }
+}
+
+/// Collects resources before the shared workers are started.
+/// Build with `CacheConnection::get_registry`.
+pub struct CacheRegistryBuilder<DB: Database> {
+    db: Pool<DB>,
+    client: redis::Client,
+    conn: CacheConn,
+    keys: KeySpace,
+    on_dirty_evicted: Option<EvictionCallback>,
+    resources: Vec<CacheResource<DB>>,
+}
+
+impl<DB: Database> CacheRegistryBuilder<DB> {
+    pub(crate) fn new(
+        db: Pool<DB>,
+        client: redis::Client,
+        conn: CacheConn,
+        keys: KeySpace,
+        on_dirty_evicted: Option<EvictionCallback>,
+    ) -> Self {
+        CacheRegistryBuilder { db, client, conn, keys, on_dirty_evicted, resources: Vec::new() }
+    }
+
+    /// Register a resource.
+    ///
+    /// Panics if the route can never produce keys under the resource key
+    /// or if the key is already registered.
+    pub fn register(mut self, resource: CacheResource<DB>) -> Self {
+        let pattern = RoutePattern::parse(&resource.route);
+        if !pattern.is_under_root(&resource.key) {
+            panic!(
+                "Route {} is not under cache key {}",
+                resource.route, resource.key
+            );
+        }
+        if self.resources.iter().any(|r| r.key == resource.key) {
+            panic!("Cache key {} is already registered", resource.key);
+        }
+        self.resources.push(resource);
+        self
+    }
+
+    /// Spawn the shared write-behind scheduler and delete poller.
+    pub fn start(self) -> CacheRegistry {
+        let mut routes = Vec::new();
+        let mut sync_resources = Vec::new();
+        for resource in self.resources {
+            let config = Arc::new(Mutex::new(resource.config));
+            let l1 = L1::default();
+            let state = CacheState {
+                conn: self.conn.clone(),
+                keys: self.keys.clone(),
+                root_key: resource.key.clone(),
+                write_to_cache: resource.put_cache_function,
+                write_function: cache_sync::bind_pool(self.db.clone(), Arc::clone(&resource.put_function)),
+                loader: resource.loader.map(|loader| cache_sync::bind_loader(self.db.clone(), loader)),
+                refreshing: InFlight::default(),
+                loading: InFlight::default(),
+                ttl_policy: resource.ttl_policy.clone(),
+                l1: l1.clone(),
+                config: Arc::clone(&config),
+            };
+            routes.push((RoutePattern::parse(&resource.route), state));
+            sync_resources.push(SyncResource {
+                root_key: resource.key,
+                config,
+                ttl_policy: resource.ttl_policy,
+                l1,
+                put_function: resource.put_function,
+                delete_function: resource.delete_function,
+            });
+        }
+
+        let workers = Workers::spawn(self.db, self.client, self.conn.clone(), self.keys.clone(), sync_resources, self.on_dirty_evicted);
+
+        CacheRegistry {
+            conn: self.conn,
+            keys: self.keys,
+            state: RegistryState { routes: Arc::new(routes) },
+            workers,
+        }
+    }
+}
+
+/// Many cached resources behind one middleware layer.
+/// Background workers start on `CacheRegistryBuilder::start`.
+pub struct CacheRegistry {
+    pub conn: CacheConn,
+    pub keys: KeySpace,
+    state: RegistryState,
+    workers: Workers,
+}
+
+impl CacheRegistry {
+    /// Return RegistryState for `registry_middleware` injection.
+    pub fn get_state(&self) -> RegistryState {
+        self.state.clone()
+    }
+
+    /// Runtime config of a registered resource.
+    pub fn config(&self, key: &str) -> Option<Arc<Mutex<CacheConfig>>> {
+        self.state
+            .routes
+            .iter()
+            .find(|(_, state)| state.root_key == key)
+            .map(|(_, state)| Arc::clone(&state.config))
+    }
+
+    /// Signals shutdown and waits for background tasks to complete.
+    pub async fn shutdown(&mut self) {
+        println!("{} Cache registry graceful shutdown", "Shutdown".red().bold());
+        self.workers.shutdown().await;
+        println!("{} Cache registry shutdown gracefully.", "Done".green().bold());
+    }
+}
+
+impl Drop for CacheRegistry {
+    fn drop(&mut self) {
+        if !self.workers.is_shutdown() {
+            eprintln!("⚠️ CacheRegistry dropped without shutdown(). This may cause data loss.");
+            #[cfg(debug_assertions)]
+            panic!("CacheRegistry dropped without calling shutdown()");
+        }
+    }
+}
+
+/// State for `registry_middleware`: route pattern → resource state.
+#[derive(Clone)]
+pub struct RegistryState {
+    routes: Arc<Vec<(RoutePattern, CacheState)>>,
+}
+
+impl RegistryState {
+    /// Find the resource state whose route matches `path`.
+    fn find(&self, path: &str) -> Option<&CacheState> {
+        self.routes
+            .iter()
+            .find(|(pattern, _)| pattern.matches(path))
+            .map(|(_, state)| state)
+    }
+}
+
+/// Single middleware for every resource of a `CacheRegistry`.
+///
+/// Dispatches by route to the matching resource and applies the same
+/// logic as `middleware`. Unmatched paths are passed through.
+pub async fn registry_middleware(
+    State(state): State<RegistryState>,
+    req: Request<Body>,
+    next: Next,
+) -> Result<Response<Body>, StatusCode> {
+    let cache_state = state.find(req.uri().path()).cloned();
+    match cache_state {
+        Some(cache_state) => handle_request(cache_state, req, next).await,
+        None => {
+            let response = next.run(req).await;
+            // A create on a collection (ex: POST /posts) names the new entity
+            if let Some(path) = created_path(&response)
+                && let Some(cache_state) = state.find(&path)
+            {
+                forget_created(cache_state, &path).await;
+            }
+            Ok(response)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Static(String),
+    Param,
+    Wildcard,
+}
+
+/// Minimal axum-style route pattern ("/posts/:id", "/posts/{id}", "/files/*path").
+#[derive(Debug, Clone)]
+struct RoutePattern {
+    segments: Vec<Segment>,
+}
+
+impl RoutePattern {
+    fn parse(route: &str) -> Self {
+        let segments = route
+            .trim_matches('/')
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                if s.starts_with('*') || s.starts_with("{*") {
+                    Segment::Wildcard
+                } else if s.starts_with(':') || (s.starts_with('{') && s.ends_with('}')) {
+                    Segment::Param
+                } else {
+                    Segment::Static(s.to_string())
+                }
+            })
+            .collect();
+        RoutePattern { segments }
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        let mut parts = path.trim_matches('/').split('/').filter(|s| !s.is_empty());
+        for segment in &self.segments {
+            match segment {
+                Segment::Wildcard => return parts.next().is_some(),
+                Segment::Param => {
+                    if parts.next().is_none() {
+                        return false;
+                    }
+                }
+                Segment::Static(expected) => {
+                    if parts.next() != Some(expected.as_str()) {
+                        return false;
+                    }
+                }
+            }
+        }
+        parts.next().is_none()
+    }
+
+    /// Every matching path normalizes to a key strictly under `root`.
+    fn is_under_root(&self, root: &str) -> bool {
+        let statics: Vec<&str> = self
+            .segments
+            .iter()
+            .map_while(|s| match s {
+                Segment::Static(s) => Some(s.as_str()),
+                _ => None,
+            })
+            .collect();
+        let has_dynamic = statics.len() < self.segments.len();
+        let prefix = statics.join(":");
+        match prefix.strip_prefix(root) {
+            Some("") => has_dynamic,
+            Some(rest) => rest.starts_with(':'),
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,134 @@
+// src/single_flight.rs
+
+use redis::{AsyncCommands, RedisResult, Script};
+use tokio::time::{Duration, Instant, sleep, timeout_at};
+
+use crate::cache::{CacheState, SingleFlight};
+use crate::connection::CacheConn;
+use crate::entity::{self, Entry, Snapshot};
+use crate::inflight::InFlightGuard;
+use crate::lease;
+
+/// Delay between two cache reads while another replica holds the miss lock.
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Delete the miss lock only if we still hold it.
+const RELEASE_SCRIPT: &str = r#"
+if redis.call('get', KEYS[1]) == ARGV[1] then
+    return redis.call('del', KEYS[1])
+end
+return 0
+"#;
+
+/// Right to run the handler for a missed key. Local waiters wake up
+/// when it is dropped; `release` also frees the distributed lock.
+pub(crate) struct Leader {
+    _local: Option<InFlightGuard>,
+    lock: Option<(String, String)>,
+}
+
+impl Leader {
+    fn alone() -> Self {
+        Leader { _local: None, lock: None }
+    }
+
+    /// Free the distributed lock, then wake local waiters.
+    pub(crate) async fn release(self, conn: &mut CacheConn) {
+        if let Some((lock_key, token)) = &self.lock {
+            let result: RedisResult<i32> = Script::new(RELEASE_SCRIPT)
+                .key(lock_key)
+                .arg(token)
+                .invoke_async(conn)
+                .await;
+            if let Err(e) = result {
+                eprintln!("❌ Failed to release miss lock {lock_key}: {e}");
+            }
+        }
+    }
+}
+
+/// Coalesce a GET miss with concurrent misses on the same key.
+///
+/// Returns the entry as filled by another request while waiting, or the
+/// miss with the `Leader` that should run the handler (or loader). A
+/// waiter whose wait timed out gets a `Leader` without the lock.
+pub(crate) async fn join(
+    state: &CacheState,
+    conn: &mut CacheConn,
+    key: &str,
+    snapshot: Snapshot,
+) -> RedisResult<(Snapshot, Option<Leader>)> {
+    let mode = state.config.lock().unwrap().single_flight;
+    let wait = match mode {
+        SingleFlight::Off => return Ok((snapshot, Some(Leader::alone()))),
+        SingleFlight::Local { wait } | SingleFlight::Distributed { wait } => wait,
+    };
+    let deadline = Instant::now() + wait;
+
+    // One request per key and process
+    let local = loop {
+        match state.loading.start(key) {
+            Ok(guard) => break guard,
+            Err(mut done) => {
+                let _ = timeout_at(deadline, done.changed()).await;
+                let snapshot = entity::read(conn, &state.keys, key).await?;
+                if !matches!(snapshot.entry, Entry::Miss) {
+                    return Ok((snapshot, None));
+                }
+                if Instant::now() >= deadline {
+                    println!("⚠️ Gave up waiting for the miss of {key}");
+                    return Ok((snapshot, Some(Leader::alone())));
+                }
+                // The leader got nothing to cache: take over
+            }
+        }
+    };
+
+    // Filled between our read and the claim
+    let snapshot = entity::read(conn, &state.keys, key).await?;
+    if !matches!(snapshot.entry, Entry::Miss) {
+        return Ok((snapshot, None));
+    }
+    let SingleFlight::Distributed { .. } = mode else {
+        return Ok((snapshot, Some(Leader { _local: Some(local), lock: None })));
+    };
+
+    // One request per key across replicas
+    let lock_key = state.keys.lock(key);
+    let token = lease::instance_id();
+    loop {
+        let acquired: bool = redis::cmd("SET")
+            .arg(&lock_key)
+            .arg(&token)
+            .arg("NX")
+            .arg("PX")
+            .arg(wait.as_millis() as u64)
+            .query_async::<Option<String>>(conn)
+            .await?
+            .is_some();
+        if acquired {
+            let snapshot = entity::read(conn, &state.keys, key).await?;
+            let leader = Leader { _local: Some(local), lock: Some((lock_key, token)) };
+            if !matches!(snapshot.entry, Entry::Miss) {
+                leader.release(conn).await;
+                return Ok((snapshot, None));
+            }
+            return Ok((snapshot, Some(leader)));
+        }
+        // Wait for the holder to fill the cache or give up the lock
+        loop {
+            sleep(LOCK_POLL_INTERVAL).await;
+            let snapshot = entity::read(conn, &state.keys, key).await?;
+            if !matches!(snapshot.entry, Entry::Miss) {
+                return Ok((snapshot, None));
+            }
+            if Instant::now() >= deadline {
+                println!("⚠️ Gave up waiting for the miss of {key}");
+                return Ok((snapshot, Some(Leader { _local: Some(local), lock: None })));
+            }
+            if !conn.exists::<_, bool>(&lock_key).await? {
+                break;
+            }
+        }
+    }
+}
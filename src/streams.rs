@@ -0,0 +1,82 @@
+// src/streams.rs
+
+use redis::AsyncCommands;
+use redis::streams::{StreamAutoClaimReply, StreamId, StreamReadOptions, StreamReadReply};
+use tokio::time::Duration;
+
+use crate::connection::CacheConn;
+
+/// One consumer of a Redis Stream read through a consumer group.
+///
+/// Each entry is delivered to a single consumer of the group and stays
+/// pending until acknowledged. Entries left pending too long (failed
+/// callback, crashed replica) can be reclaimed by any consumer.
+pub(crate) struct StreamConsumer<'a> {
+    pub stream: String,
+    group: &'static str,
+    consumer: &'a str,
+}
+
+impl<'a> StreamConsumer<'a> {
+    pub(crate) fn new(stream: String, group: &'static str, consumer: &'a str) -> Self {
+        StreamConsumer { stream, group, consumer }
+    }
+
+    /// Create the consumer group (and the stream) if missing.
+    pub(crate) async fn create_group(&self, conn: &mut CacheConn) {
+        let result: redis::RedisResult<()> =
+            conn.xgroup_create_mkstream(&self.stream, self.group, "0").await;
+        if let Err(e) = result
+            && e.code() != Some("BUSYGROUP")
+        {
+            eprintln!("❌ Failed to create consumer group {} on {}: {e}", self.group, self.stream);
+        }
+    }
+
+    /// Entries not yet delivered to any consumer of the group.
+    pub(crate) async fn read_new(&self, conn: &mut CacheConn, count: usize) -> redis::RedisResult<Vec<StreamId>> {
+        let options = StreamReadOptions::default()
+            .group(self.group, self.consumer)
+            .count(count);
+        let reply: StreamReadReply = conn.xread_options(&[&self.stream], &[">"], &options).await?;
+        Ok(reply.keys.into_iter().flat_map(|key| key.ids).collect())
+    }
+
+    /// Claim every entry pending for at least `idle`, whoever it was delivered to.
+    pub(crate) async fn reclaim(
+        &self,
+        conn: &mut CacheConn,
+        idle: Duration,
+        count: usize,
+    ) -> redis::RedisResult<Vec<StreamId>> {
+        let mut claimed = Vec::new();
+        let mut start = "0-0".to_string();
+        loop {
+            let reply: StreamAutoClaimReply = redis::cmd("XAUTOCLAIM")
+                .arg(&self.stream)
+                .arg(self.group)
+                .arg(self.consumer)
+                .arg(idle.as_millis() as u64)
+                .arg(&start)
+                .arg("COUNT")
+                .arg(count)
+                .query_async(conn)
+                .await?;
+            claimed.extend(reply.claimed);
+            if reply.next_stream_id == "0-0" {
+                return Ok(claimed);
+            }
+            start = reply.next_stream_id;
+        }
+    }
+
+    /// Acknowledge a processed entry and drop it from the stream.
+    pub(crate) async fn ack(&self, conn: &mut CacheConn, entry_id: &str) {
+        let acked: redis::RedisResult<i32> = conn.xack(&self.stream, self.group, &[entry_id]).await;
+        if let Err(e) = acked {
+            eprintln!("❌ Failed to acknowledge {entry_id} on {}: {e}", self.stream);
+            return;
+        }
+        let _: redis::RedisResult<i32> = conn.xdel(&self.stream, &[entry_id]).await;
+    }
+}
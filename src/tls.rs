@@ -0,0 +1,96 @@
+// src/tls.rs
+
+use redis::{Client, ConnectionAddr, ConnectionInfo, RedisResult};
+use redis::{ClientTlsConfig, TlsCertificates};
+use std::path::Path;
+
+/// TLS config for `rediss://` connections (feature `tls`).
+/// - `root_cert`: custom CA bundle (PEM), system roots if `None`
+/// - `client_cert`, `client_key`: client certificate for mTLS (PEM)
+#[derive(Clone, Default)]
+pub struct TlsConfig {
+    pub root_cert: Option<Vec<u8>>,
+    pub client_cert: Option<Vec<u8>>,
+    pub client_key: Option<Vec<u8>>,
+}
+
+impl std::fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsConfig")
+            .field("root_cert", &self.root_cert.as_ref().map(|_| "<pem>"))
+            .field("client_cert", &self.client_cert.as_ref().map(|_| "<pem>"))
+            .field("client_key", &self.client_key.as_ref().map(|_| "***"))
+            .finish()
+    }
+}
+
+impl TlsConfig {
+    /// Build with system root certificates.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set custom CA bundle (PEM).
+    pub fn with_root_cert(mut self, pem: &[u8]) -> Self {
+        self.root_cert = Some(pem.to_vec());
+        self
+    }
+
+    /// Load custom CA bundle from a PEM file.
+    pub fn with_root_cert_file(self, path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let pem = std::fs::read(path)?;
+        Ok(self.with_root_cert(&pem))
+    }
+
+    /// Set client certificate and key (PEM) for mTLS.
+    pub fn with_client_cert(mut self, cert: &[u8], key: &[u8]) -> Self {
+        self.client_cert = Some(cert.to_vec());
+        self.client_key = Some(key.to_vec());
+        self
+    }
+
+    /// Load client certificate and key from PEM files.
+    pub fn with_client_cert_files(
+        self,
+        cert_path: impl AsRef<Path>,
+        key_path: impl AsRef<Path>,
+    ) -> std::io::Result<Self> {
+        let cert = std::fs::read(cert_path)?;
+        let key = std::fs::read(key_path)?;
+        Ok(self.with_client_cert(&cert, &key))
+    }
+
+    /// Certificates in the form redis expects.
+    pub(crate) fn certificates(&self) -> TlsCertificates {
+        let client_tls = match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => Some(ClientTlsConfig {
+                client_cert: cert.clone(),
+                client_key: key.clone(),
+            }),
+            _ => None,
+        };
+        TlsCertificates {
+            client_tls,
+            root_cert: self.root_cert.clone(),
+        }
+    }
+
+    /// Build a client that always uses TLS, even for a `redis://` URL.
+    pub(crate) fn build_client(&self, mut info: ConnectionInfo) -> RedisResult<Client> {
+        install_crypto_provider();
+        if let ConnectionAddr::Tcp(host, port) = info.addr {
+            info.addr = ConnectionAddr::TcpTls {
+                host,
+                port,
+                insecure: false,
+                tls_params: None,
+            };
+        }
+        Client::build_with_tls(info, self.certificates())
+    }
+}
+
+/// rustls needs a process-wide crypto provider; keep an already installed one.
+pub(crate) fn install_crypto_provider() {
+    let _ = rustls::crypto::ring::default_provider().install_default();
+}
@@ -0,0 +1,119 @@
+// src/ttl.rs
+
+use axum::http::{Extensions, HeaderMap};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, RwLock};
+
+use crate::cache::CacheConfig;
+
+/// Response header a handler can set to pick the clean TTL (seconds,
+/// 0 = don't cache).
+pub const CACHE_TTL_HEADER: &str = "X-Cache-TTL";
+
+/// Longest TTL (seconds) the cache sets, one year. Longer values from a
+/// policy, a response or the config are clamped to it.
+pub const MAX_TTL: u64 = 365 * 24 * 3600;
+
+/// Response extension a handler can set to pick the clean TTL,
+/// without exposing it to clients (ex: `(Extension(CacheTtl(3600)), body)`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheTtl(pub u64);
+
+/// What a `TtlPolicy` can base its choice on.
+/// - `root`: resource root key (ex: "posts")
+/// - `key`: entity key (ex: "posts:1")
+/// - `headers`, `extensions`: handler response, when the entry comes from one
+pub struct TtlContext<'a> {
+    pub root: &'a str,
+    pub key: &'a str,
+    pub headers: Option<&'a HeaderMap>,
+    pub extensions: Option<&'a Extensions>,
+}
+
+/// Picks TTLs per entry instead of the manager-wide `CacheConfig` values.
+/// `None` falls back to `ttl_clean` / `ttl_deleted`.
+///
+/// Closures `Fn(&TtlContext) -> Option<u64>` are clean TTL policies.
+pub trait TtlPolicy: Send + Sync {
+    /// Seconds a clean entry stays fresh.
+    fn clean_ttl(&self, ctx: &TtlContext<'_>) -> Option<u64>;
+
+    /// Seconds a delete marker lives before the DB delete runs.
+    fn deleted_ttl(&self, ctx: &TtlContext<'_>) -> Option<u64> {
+        let _ = ctx;
+        None
+    }
+}
+
+impl<F> TtlPolicy for F
+where
+    F: Fn(&TtlContext<'_>) -> Option<u64> + Send + Sync,
+{
+    fn clean_ttl(&self, ctx: &TtlContext<'_>) -> Option<u64> {
+        self(ctx)
+    }
+}
+
+/// Default policy: the handler's `CacheTtl` extension, else its
+/// `X-Cache-TTL` header, else the config.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResponseTtl;
+
+impl TtlPolicy for ResponseTtl {
+    fn clean_ttl(&self, ctx: &TtlContext<'_>) -> Option<u64> {
+        if let Some(CacheTtl(ttl)) = ctx.extensions.and_then(|ext| ext.get::<CacheTtl>()) {
+            return Some(*ttl);
+        }
+        ctx.headers?
+            .get(CACHE_TTL_HEADER)?
+            .to_str()
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    }
+}
+
+/// Policy of one resource, replaceable at runtime like its config.
+#[derive(Clone)]
+pub(crate) struct SharedTtlPolicy(Arc<RwLock<Arc<dyn TtlPolicy>>>);
+
+impl Default for SharedTtlPolicy {
+    fn default() -> Self {
+        SharedTtlPolicy(Arc::new(RwLock::new(Arc::new(ResponseTtl))))
+    }
+}
+
+impl SharedTtlPolicy {
+    pub(crate) fn set(&self, policy: Arc<dyn TtlPolicy>) {
+        *self.0.write().unwrap() = policy;
+    }
+
+    /// Fresh TTL of a clean entry (seconds).
+    pub(crate) fn clean(&self, config: &CacheConfig, ctx: &TtlContext<'_>) -> u64 {
+        let policy = Arc::clone(&self.0.read().unwrap());
+        policy.clean_ttl(ctx).unwrap_or(config.ttl_clean)
+    }
+
+    /// TTL of a delete marker (seconds).
+    pub(crate) fn deleted(&self, config: &CacheConfig, ctx: &TtlContext<'_>) -> u64 {
+        let policy = Arc::clone(&self.0.read().unwrap());
+        policy.deleted_ttl(ctx).unwrap_or(config.ttl_deleted)
+    }
+}
+
+impl<'a> TtlContext<'a> {
+    /// Context without a handler response (flush, loader, write-through).
+    pub(crate) fn key(root: &'a str, key: &'a str) -> Self {
+        TtlContext { root, key, headers: None, extensions: None }
+    }
+}
+
+/// Random number in `0..bound` (not for anything but spreading TTLs).
+pub(crate) fn random_below(bound: u64) -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+    hasher.write_u128(now.as_nanos());
+    hasher.finish() % bound
+}
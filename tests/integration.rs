@@ -2,7 +2,7 @@
 
 use axum::{
     Router,
-    routing::{get, put, delete},
+    routing::{get, post, put, delete},
     body::Body,
     http::{Request, StatusCode},
     middleware::from_fn_with_state,
@@ -14,6 +14,7 @@ use tokio::time::sleep;
 use redis::AsyncCommands;
 
 #[path = "common.rs"]
+#[allow(unused_imports, unused_mut, dead_code)]
 mod common;
 
 /// 테이블 생성 SQL (테스트용)
@@ -232,4 +233,1169 @@ async fn test_cache_delete_ttl() {
 
     // (4) CacheManager shutdown
     manager.shutdown().await;
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_cache_key_prefix() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url)
+        .with_prefix("myapp:test:")
+        .with_dirty_marker("d")
+        .with_delete_marker("x");
+    let mut cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+    let mut manager = cache.get_manager(
+        "posts_prefix".to_string(),
+        |_db, _s| Box::pin(async {}),
+        |_db, _s| Box::pin(async {}),
+        common::merge_json,
+    );
+
+    let app = Router::new()
+        .route("/posts_prefix/:id", get(|| async { "prefixed" }).put(|| async { "updated" }))
+        .route("/posts_prefix/:id", delete(|| async { "" }))
+        .layer(from_fn_with_state(manager.get_state(), axum_redis_cache::middleware));
+
+    // (1) GET 캐시 미스 → clean 키는 prefix 아래에 저장
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri("/posts_prefix/1")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let key_exists: bool = cache.conn.exists("myapp:test:posts_prefix:1").await.unwrap();
+    assert!(key_exists);
+    let key_exists: bool = cache.conn.exists("posts_prefix:1").await.unwrap();
+    assert!(!key_exists);
+
+    // (2) PUT → dirty 마커 이름 사용
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("PUT")
+                .uri("/posts_prefix/1")
+                .body(Body::from(r#"{"foo":"bar"}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let key_exists: bool = cache.conn.exists("myapp:test:d:posts_prefix:1").await.unwrap();
+    assert!(key_exists);
+
+    // (3) DELETE → delete 마커 이름 사용
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("DELETE")
+                .uri("/posts_prefix/1")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let key_exists: bool = cache.conn.exists("myapp:test:x:posts_prefix:1").await.unwrap();
+    assert!(key_exists);
+
+    manager.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_cache_scoped_to_root_key() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url);
+    let mut cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+    let mut manager = cache.get_manager(
+        "posts".to_string(),
+        |_db, _s| Box::pin(async {}),
+        |_db, _s| Box::pin(async {}),
+        common::merge_json,
+    );
+
+    let app = Router::new()
+        .route("/comments/:id", get(|| async { "comment" }).put(|| async { "updated" }))
+        .route("/comments/:id", delete(|| async { "handler" }))
+        .layer(from_fn_with_state(manager.get_state(), axum_redis_cache::middleware));
+
+    // (1) posts 매니저는 /comments 경로를 캐시하지 않음
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri("/comments/1")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("X-Cache").is_none());
+    let key_exists: bool = cache.conn.exists("comments:1").await.unwrap();
+    assert!(!key_exists);
+
+    // (2) DELETE는 핸들러로 전달되고 delete 마커를 만들지 않음
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("DELETE")
+                .uri("/comments/1")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let key_exists: bool = cache.conn.exists("delete:comments:1").await.unwrap();
+    assert!(!key_exists);
+
+    manager.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_cache_delete_event_non_zero_db() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = format!("{}/3", redisstruct.url);
+
+    let deleted = std::sync::Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+    let deleted_ids = std::sync::Arc::clone(&deleted);
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url);
+    let cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+    let cache_config = CacheConfig::new().with_deleted_ttl(1);
+    let mut manager = cache.get_manager(
+        "posts_db3".to_string(),
+        |_db, _s| Box::pin(async {}),
+        move |_db, id| {
+            let deleted_ids = std::sync::Arc::clone(&deleted_ids);
+            Box::pin(async move { deleted_ids.lock().unwrap().push(id); })
+        },
+        common::merge_json,
+    ).with_config(cache_config);
+
+    let app = Router::new()
+        .route("/posts_db3/:id", delete(|| async { "" }))
+        .layer(from_fn_with_state(manager.get_state(), axum_redis_cache::middleware));
+
+    // (1) DB 3에 delete 마커 생성
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("DELETE")
+                .uri("/posts_db3/7")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    // (2) 만료 이벤트가 DB 3 채널에서 수신되어 delete 콜백 호출
+    sleep(Duration::from_secs(3)).await;
+    assert_eq!(*deleted.lock().unwrap(), vec!["7".to_string()]);
+
+    manager.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_cache_reconnects_after_connection_killed() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let deleted = std::sync::Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+    let deleted_ids = std::sync::Arc::clone(&deleted);
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url);
+    let cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+    let cache_config = CacheConfig::new().with_deleted_ttl(1);
+    let mut manager = cache.get_manager(
+        "posts_reconnect".to_string(),
+        |_db, _s| Box::pin(async {}),
+        move |_db, id| {
+            let deleted_ids = std::sync::Arc::clone(&deleted_ids);
+            Box::pin(async move { deleted_ids.lock().unwrap().push(id); })
+        },
+        common::merge_json,
+    ).with_config(cache_config);
+
+    let app = Router::new()
+        .route("/posts_reconnect/:id", get(|| async { "hello" }))
+        .route("/posts_reconnect/:id", delete(|| async { "" }))
+        .layer(from_fn_with_state(manager.get_state(), axum_redis_cache::middleware));
+
+    // (1) 미들웨어 연결과 pub/sub 연결을 서버에서 강제로 끊음
+    let client = redis::Client::open(redis_url.as_str()).unwrap();
+    let mut admin = client.get_multiplexed_async_connection().await.unwrap();
+    let _: () = redis::cmd("CLIENT").arg("KILL").arg("TYPE").arg("normal")
+        .query_async(&mut admin).await.unwrap();
+    let _: () = redis::cmd("CLIENT").arg("KILL").arg("TYPE").arg("pubsub")
+        .query_async(&mut admin).await.unwrap();
+
+    // (2) 요청은 재연결 후 정상 처리
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri("/posts_reconnect/1")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(manager.conn.reconnect_count() >= 1);
+
+    // (3) 리스너가 재구독한 뒤 삭제 이벤트 수신
+    sleep(Duration::from_secs(2)).await;
+    assert!(manager.conn.resubscribe_count() >= 1);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("DELETE")
+                .uri("/posts_reconnect/7")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    sleep(Duration::from_secs(3)).await;
+    assert_eq!(*deleted.lock().unwrap(), vec!["7".to_string()]);
+
+    manager.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_cache_delete_polled_without_keyspace_events() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    // 다른 앱이 쓰는 알림 플래그 (keyspace_events 비활성 시 그대로 유지되어야 함)
+    let client = redis::Client::open(redis_url.as_str()).unwrap();
+    let mut admin = client.get_multiplexed_async_connection().await.unwrap();
+    let _: () = redis::cmd("CONFIG").arg("SET").arg("notify-keyspace-events").arg("Kg")
+        .query_async(&mut admin).await.unwrap();
+
+    let deleted = std::sync::Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+    let deleted_ids = std::sync::Arc::clone(&deleted);
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url)
+        .with_keyspace_events(false);
+    let cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+    let cache_config = CacheConfig::new().with_deleted_ttl(1);
+    let mut manager = cache.get_manager(
+        "posts_polled".to_string(),
+        |_db, _s| Box::pin(async {}),
+        move |_db, id| {
+            let deleted_ids = std::sync::Arc::clone(&deleted_ids);
+            Box::pin(async move { deleted_ids.lock().unwrap().push(id); })
+        },
+        common::merge_json,
+    ).with_config(cache_config);
+
+    let app = Router::new()
+        .route("/posts_polled/:id", delete(|| async { "" }))
+        .layer(from_fn_with_state(manager.get_state(), axum_redis_cache::middleware));
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("DELETE")
+                .uri("/posts_polled/9")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    // (1) 대기 중인 삭제가 큐에 기록됨
+    let queued: Vec<String> = admin.zrange("delete-queue:posts_polled", 0, -1).await.unwrap();
+    assert_eq!(queued, vec!["9".to_string()]);
+
+    // (2) 알림 없이 poller가 삭제 실행, 큐에서 제거
+    sleep(Duration::from_secs(3)).await;
+    assert_eq!(*deleted.lock().unwrap(), vec!["9".to_string()]);
+    let queued: Vec<String> = admin.zrange("delete-queue:posts_polled", 0, -1).await.unwrap();
+    assert!(queued.is_empty());
+
+    // (3) 서버 알림 플래그는 변경되지 않음
+    let flags: Vec<String> = redis::cmd("CONFIG").arg("GET").arg("notify-keyspace-events")
+        .query_async(&mut admin).await.unwrap();
+    assert!(flags[1].contains('K') && flags[1].contains('g'));
+    assert!(!flags[1].contains('E') && !flags[1].contains('x'));
+
+    manager.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_cache_failed_delete_stays_pending() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url);
+    let cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+    let cache_config = CacheConfig::new().with_deleted_ttl(1);
+    let mut manager = cache.get_manager(
+        "posts_failing".to_string(),
+        |_db, _s| Box::pin(async {}),
+        |_db, _id| Box::pin(async { Err::<(), _>("db unavailable") }),
+        common::merge_json,
+    ).with_config(cache_config);
+
+    let app = Router::new()
+        .route("/posts_failing/:id", delete(|| async { "" }))
+        .layer(from_fn_with_state(manager.get_state(), axum_redis_cache::middleware));
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("DELETE")
+                .uri("/posts_failing/5")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    // (1) 삭제 콜백 실패 -> 스트림 항목은 ack되지 않고 pending으로 남음
+    sleep(Duration::from_secs(3)).await;
+    let client = redis::Client::open(redis_url.as_str()).unwrap();
+    let mut admin = client.get_multiplexed_async_connection().await.unwrap();
+    let pending: redis::streams::StreamPendingReply = admin
+        .xpending("delete-stream:posts_failing", "cache-delete")
+        .await
+        .unwrap();
+    assert_eq!(pending.count(), 1);
+    let queued: Vec<String> = admin.zrange("delete-queue:posts_failing", 0, -1).await.unwrap();
+    assert!(queued.is_empty());
+
+    manager.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_cache_put_during_flush_stays_dirty() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let written = std::sync::Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+    let written_bodies = std::sync::Arc::clone(&written);
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url);
+    let mut cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+    let cache_config = CacheConfig::new().with_write_duration(1);
+    let mut manager = cache.get_manager(
+        "posts_versioned".to_string(),
+        move |_db, body| {
+            let written_bodies = std::sync::Arc::clone(&written_bodies);
+            // 느린 DB write (flush 도중 PUT이 들어오도록)
+            Box::pin(async move {
+                sleep(Duration::from_secs(2)).await;
+                written_bodies.lock().unwrap().push(body);
+            })
+        },
+        |_db, _s| Box::pin(async {}),
+        common::merge_json,
+    ).with_config(cache_config);
+
+    let app = Router::new()
+        .route("/posts_versioned/:id", get(|| async { r#"{"v":0}"# }).put(|| async { "" }))
+        .layer(from_fn_with_state(manager.get_state(), axum_redis_cache::middleware));
+
+    let send = |method: &str, body: &'static str| {
+        Request::builder()
+            .method(method)
+            .uri("/posts_versioned/1")
+            .body(Body::from(body))
+            .unwrap()
+    };
+    let response = app.clone().oneshot(send("GET", "")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // (1) 첫 PUT → flush 시작 (DB write 2초)
+    app.clone().oneshot(send("PUT", r#"{"v":1}"#)).await.unwrap();
+    sleep(Duration::from_millis(1500)).await;
+
+    // (2) flush 도중 두 번째 PUT → 버전 불일치로 dirty 유지
+    app.clone().oneshot(send("PUT", r#"{"v":2}"#)).await.unwrap();
+    sleep(Duration::from_secs(2)).await;
+    let dirty: Option<String> = cache.conn.get("dirty:posts_versioned:1").await.unwrap();
+    assert_eq!(dirty.as_deref(), Some(r#"{"v":2}"#));
+
+    // (3) 다음 flush에서 최신 값이 DB에 기록됨
+    sleep(Duration::from_secs(4)).await;
+    assert_eq!(written.lock().unwrap().last().map(String::as_str), Some(r#"{"v":2}"#));
+    assert!(!cache.conn.exists::<_, bool>("dirty:posts_versioned:1").await.unwrap());
+
+    manager.shutdown().await;
+}
+
+/// 필드 단위 JSON 병합 (동시 PUT 유실 확인용)
+fn merge_fields(old: String, new: String) -> String {
+    let mut merged: serde_json::Value = serde_json::from_str(&old).unwrap();
+    let new: serde_json::Value = serde_json::from_str(&new).unwrap();
+    for (k, v) in new.as_object().unwrap() {
+        merged[k] = v.clone();
+    }
+    merged.to_string()
+}
+
+#[tokio::test]
+async fn test_cache_concurrent_puts_and_delete_during_flush() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url);
+    let mut cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+    let cache_config = CacheConfig::new().with_write_duration(1);
+    let mut manager = cache.get_manager(
+        "posts_atomic".to_string(),
+        |_db, _body| Box::pin(async {
+            // 느린 DB write (flush 도중 DELETE가 들어오도록)
+            sleep(Duration::from_secs(2)).await;
+        }),
+        |_db, _s| Box::pin(async {}),
+        merge_fields,
+    ).with_config(cache_config);
+
+    let app = Router::new()
+        .route("/posts_atomic/:id", get(|| async { "{}" }).put(|| async { "" }).delete(|| async { "" }))
+        .layer(from_fn_with_state(manager.get_state(), axum_redis_cache::middleware));
+
+    let send = |method: &str, body: String| {
+        Request::builder()
+            .method(method)
+            .uri("/posts_atomic/1")
+            .body(Body::from(body))
+            .unwrap()
+    };
+    let response = app.clone().oneshot(send("GET", String::new())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // (1) 동시 PUT 10개 → 각자 다른 필드, 병합 결과에서 하나도 유실되지 않음
+    let puts = (0..10).map(|i| app.clone().oneshot(send("PUT", format!(r#"{{"f{i}":{i}}}"#))));
+    for response in futures_util::future::join_all(puts).await {
+        assert_eq!(response.unwrap().status(), StatusCode::OK);
+    }
+    let merged: String = cache.conn.get("dirty:posts_atomic:1").await.unwrap();
+    let merged: serde_json::Value = serde_json::from_str(&merged).unwrap();
+    for i in 0..10 {
+        assert_eq!(merged[format!("f{i}")], i);
+    }
+    let version: u64 = cache.conn.get("dirty-version:posts_atomic:1").await.unwrap();
+    assert_eq!(version, 10);
+
+    // (2) flush 도중 DELETE → flush가 clean 항목을 되살리지 않음
+    sleep(Duration::from_millis(1500)).await;
+    let response = app.clone().oneshot(send("DELETE", String::new())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    sleep(Duration::from_secs(3)).await;
+    assert!(!cache.conn.exists::<_, bool>("posts_atomic:1").await.unwrap());
+    let response = app.clone().oneshot(send("GET", String::new())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // (3) DELETE 후에도 버전은 초기화되지 않고 계속 증가
+    let version: u64 = cache.conn.get("dirty-version:posts_atomic:1").await.unwrap();
+    assert_eq!(version, 11);
+
+    manager.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_cache_write_through() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let written = std::sync::Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+    let written_bodies = std::sync::Arc::clone(&written);
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url);
+    let mut cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+    let cache_config = CacheConfig::new().with_write_mode(axum_redis_cache::WriteMode::WriteThrough);
+    let mut manager = cache.get_manager(
+        "payments".to_string(),
+        move |_db, body: String| {
+            let written_bodies = std::sync::Arc::clone(&written_bodies);
+            async move {
+                // "fail" 필드가 있으면 DB 오류
+                if body.contains("fail") {
+                    return Err("constraint violation");
+                }
+                written_bodies.lock().unwrap().push(body);
+                Ok(())
+            }
+        },
+        |_db, _s| async {},
+        common::merge_json,
+    ).with_config(cache_config);
+
+    let app = Router::new()
+        .route("/payments/:id", get(|| async { r#"{"amount":0}"# }).put(|| async { "" }))
+        .layer(from_fn_with_state(manager.get_state(), axum_redis_cache::middleware));
+
+    let send = |method: &str, body: &'static str| {
+        Request::builder()
+            .method(method)
+            .uri("/payments/1")
+            .body(Body::from(body))
+            .unwrap()
+    };
+    let response = app.clone().oneshot(send("GET", "")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // (1) PUT 응답 전에 DB write 완료, dirty 없이 clean 갱신
+    let response = app.clone().oneshot(send("PUT", r#"{"amount":5}"#)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(written.lock().unwrap().as_slice(), [r#"{"amount":5}"#]);
+    assert!(!cache.conn.exists::<_, bool>("dirty:payments:1").await.unwrap());
+    let clean: String = cache.conn.get("payments:1").await.unwrap();
+    assert_eq!(clean, r#"{"amount":5}"#);
+
+    // (2) DB 오류 → 500, 캐시는 이전 값 유지
+    let response = app.clone().oneshot(send("PUT", r#"{"fail":true}"#)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let clean: String = cache.conn.get("payments:1").await.unwrap();
+    assert_eq!(clean, r#"{"amount":5}"#);
+    assert_eq!(written.lock().unwrap().len(), 1);
+
+    manager.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_cache_write_through_concurrent_puts() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let written = std::sync::Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+    let written_bodies = std::sync::Arc::clone(&written);
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url);
+    let mut cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+    let cache_config = CacheConfig::new().with_write_mode(axum_redis_cache::WriteMode::WriteThrough);
+    let mut manager = cache.get_manager(
+        "payments_racing".to_string(),
+        move |_db, body: String| {
+            let written_bodies = std::sync::Arc::clone(&written_bodies);
+            async move {
+                // 느린 DB write (동시 PUT이 같은 버전을 읽도록)
+                sleep(Duration::from_millis(200)).await;
+                written_bodies.lock().unwrap().push(body);
+            }
+        },
+        |_db, _s| async {},
+        merge_fields,
+    ).with_config(cache_config);
+
+    let app = Router::new()
+        .route("/payments_racing/:id", get(|| async { "{}" }).put(|| async { "" }))
+        .layer(from_fn_with_state(manager.get_state(), axum_redis_cache::middleware));
+
+    let send = |method: &str, body: String| {
+        Request::builder()
+            .method(method)
+            .uri("/payments_racing/1")
+            .body(Body::from(body))
+            .unwrap()
+    };
+    let response = app.clone().oneshot(send("GET", String::new())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // (1) 동시 PUT 5개 → 충돌한 PUT은 다시 병합해 DB에 다시 씀
+    let puts = (0..5).map(|i| app.clone().oneshot(send("PUT", format!(r#"{{"f{i}":{i}}}"#))));
+    for response in futures_util::future::join_all(puts).await {
+        assert_eq!(response.unwrap().status(), StatusCode::OK);
+    }
+
+    // (2) 마지막 DB write와 캐시 모두 모든 필드를 가짐
+    let last = written.lock().unwrap().last().cloned().unwrap();
+    let clean: String = cache.conn.get("payments_racing:1").await.unwrap();
+    for body in [last, clean] {
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        for i in 0..5 {
+            assert_eq!(body[format!("f{i}")], i);
+        }
+    }
+
+    manager.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_cache_read_through_loader() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url);
+    let mut cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+    let mut manager = cache.get_manager(
+        "posts_loaded".to_string(),
+        |_db, _s| async {},
+        |_db, _s| async {},
+        common::merge_json,
+    ).with_loader(|_db, id: String| async move {
+        // id 1, 2만 DB에 존재
+        (id == "1" || id == "2").then(|| format!(r#"{{"id":{id}}}"#))
+    });
+
+    // 핸들러는 호출되면 안 됨 (loader가 채움)
+    let app = Router::new()
+        .route("/posts_loaded/:id", get(|| async { StatusCode::IM_A_TEAPOT }))
+        .layer(from_fn_with_state(manager.get_state(), axum_redis_cache::middleware));
+
+    // (1) GET miss → loader 결과가 clean으로 캐시됨
+    let response = app
+        .clone()
+        .oneshot(Request::builder().method("GET").uri("/posts_loaded/1").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let clean: String = cache.conn.get("posts_loaded:1").await.unwrap();
+    assert_eq!(clean, r#"{"id":1}"#);
+
+    // (2) HTTP 없이 백그라운드 작업에서 캐시 채우기
+    assert_eq!(manager.get_or_load("2").await.as_deref(), Some(r#"{"id":2}"#));
+    let clean: String = cache.conn.get("posts_loaded:2").await.unwrap();
+    assert_eq!(clean, r#"{"id":2}"#);
+
+    // (3) loader가 None → 핸들러로 전달
+    assert_eq!(manager.get_or_load("3").await, None);
+    let response = app
+        .clone()
+        .oneshot(Request::builder().method("GET").uri("/posts_loaded/3").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::IM_A_TEAPOT);
+
+    manager.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_cache_refresh_ahead() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url);
+    let mut cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+    let cache_config = CacheConfig::new().with_clean_ttl(4).with_refresh_ahead(0.5);
+    let mut manager = cache.get_manager(
+        "posts_hot".to_string(),
+        |_db, _s| async {},
+        |_db, _s| async {},
+        common::merge_json,
+    ).with_config(cache_config);
+
+    // 핸들러 호출 횟수를 응답에 담음
+    let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let handler_calls = std::sync::Arc::clone(&calls);
+    let app = Router::new()
+        .route("/posts_hot/:id", get(move || {
+            let handler_calls = std::sync::Arc::clone(&handler_calls);
+            async move {
+                let n = handler_calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                format!(r#"{{"n":{n}}}"#)
+            }
+        }))
+        .layer(from_fn_with_state(manager.get_state(), axum_redis_cache::middleware));
+
+    let get_hot = || app.clone().oneshot(Request::builder().method("GET").uri("/posts_hot/1").body(Body::empty()).unwrap());
+    assert_eq!(get_hot().await.unwrap().status(), StatusCode::OK);
+
+    // (1) TTL 절반 이상 남음 → refresh 없음
+    sleep(Duration::from_secs(1)).await;
+    get_hot().await.unwrap();
+    sleep(Duration::from_millis(300)).await;
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    // (2) TTL 절반 미만 → 동시 GET 5개는 기존 값, refresh는 1번만
+    sleep(Duration::from_millis(1700)).await;
+    for response in futures_util::future::join_all((0..5).map(|_| get_hot())).await {
+        assert_eq!(response.unwrap().status(), StatusCode::OK);
+    }
+    sleep(Duration::from_millis(500)).await;
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+    let clean: String = cache.conn.get("posts_hot:1").await.unwrap();
+    assert_eq!(clean, r#"{"n":2}"#);
+    let ttl: i64 = cache.conn.ttl("posts_hot:1").await.unwrap();
+    assert!(ttl > 2);
+
+    manager.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_cache_single_flight_local() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url);
+    let cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+    let cache_config = CacheConfig::new()
+        .with_single_flight(axum_redis_cache::SingleFlight::Local { wait: Duration::from_secs(2) });
+    let mut manager = cache.get_manager(
+        "posts_popular".to_string(),
+        |_db, _s| async {},
+        |_db, _s| async {},
+        common::merge_json,
+    ).with_config(cache_config);
+
+    // 느린 핸들러 (호출 횟수 기록)
+    let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let handler_calls = std::sync::Arc::clone(&calls);
+    let app = Router::new()
+        .route("/posts_popular/:id", get(move || {
+            let handler_calls = std::sync::Arc::clone(&handler_calls);
+            async move {
+                handler_calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                sleep(Duration::from_millis(500)).await;
+                r#"{"title":"hot"}"#
+            }
+        }))
+        .layer(from_fn_with_state(manager.get_state(), axum_redis_cache::middleware));
+
+    // 동시 miss 10개 → 핸들러 1번, 모두 같은 결과
+    let gets = (0..10).map(|_| {
+        app.clone().oneshot(Request::builder().method("GET").uri("/posts_popular/1").body(Body::empty()).unwrap())
+    });
+    for response in futures_util::future::join_all(gets).await {
+        let response = response.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], br#"{"title":"hot"}"#);
+    }
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    manager.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_cache_stale_while_revalidate_and_if_error() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url);
+    let cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+    let mut manager = cache.get_manager(
+        "posts_stale".to_string(),
+        |_db, _s| async {},
+        |_db, _s| async {},
+        common::merge_json,
+    ).with_config(CacheConfig::new().with_clean_ttl(1).with_stale_while_revalidate(3));
+
+    // 호출 횟수: 1 → 정상, 2 → 정상, 3 → 500, 4 → 타임아웃
+    let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let handler_calls = std::sync::Arc::clone(&calls);
+    let app = Router::new()
+        .route("/posts_stale/:id", get(move || {
+            let handler_calls = std::sync::Arc::clone(&handler_calls);
+            async move {
+                let n = handler_calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                match n {
+                    3 => (StatusCode::INTERNAL_SERVER_ERROR, String::new()),
+                    4 => {
+                        sleep(Duration::from_secs(2)).await;
+                        (StatusCode::OK, String::new())
+                    }
+                    _ => (StatusCode::OK, format!(r#"{{"n":{n}}}"#)),
+                }
+            }
+        }))
+        .layer(from_fn_with_state(manager.get_state(), axum_redis_cache::middleware));
+    let get_stale = || app.clone().oneshot(Request::builder().method("GET").uri("/posts_stale/1").body(Body::empty()).unwrap());
+
+    assert_eq!(get_stale().await.unwrap().status(), StatusCode::OK);
+
+    // (1) ttl_clean 경과, grace 이내 → 즉시 STALE 응답 + 백그라운드 재검증
+    sleep(Duration::from_millis(1500)).await;
+    let response = get_stale().await.unwrap();
+    assert_eq!(response.headers()["X-Cache"], "STALE");
+    assert!(response.headers()["Warning"].to_str().unwrap().starts_with("110"));
+    assert!(response.headers()["Age"].to_str().unwrap().parse::<u64>().unwrap() >= 1);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&body[..], br#"{"n":1}"#);
+    sleep(Duration::from_millis(300)).await;
+    let response = get_stale().await.unwrap();
+    assert_eq!(response.headers()["X-Cache"], "HIT");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&body[..], br#"{"n":2}"#);
+
+    // (2) stale-if-error: 재검증 grace 없이 핸들러 호출, 5xx/타임아웃이면 stale 응답
+    *manager.config.lock().unwrap() = CacheConfig::new()
+        .with_clean_ttl(1)
+        .with_stale_if_error(5)
+        .with_handler_timeout(Duration::from_millis(300));
+    sleep(Duration::from_millis(1200)).await;
+    for _ in 0..2 {
+        let response = get_stale().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["X-Cache"], "STALE");
+        assert!(response.headers()["Warning"].to_str().unwrap().starts_with("111"));
+    }
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 4);
+
+    manager.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_cache_negative_caching() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url);
+    let mut cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+    let mut manager = cache.get_manager(
+        "posts_missing".to_string(),
+        |_db, _s| async {},
+        |_db, _s| async {},
+        common::merge_json,
+    ).with_config(CacheConfig::new().with_not_found_ttl(2));
+
+    // PUT 전에는 404 (핸들러 호출 횟수 기록)
+    let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let created = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let (handler_calls, get_created, put_created) = (std::sync::Arc::clone(&calls), std::sync::Arc::clone(&created), std::sync::Arc::clone(&created));
+    let app = Router::new()
+        .route("/posts_missing/:id", get(move || {
+            let handler_calls = std::sync::Arc::clone(&handler_calls);
+            let get_created = std::sync::Arc::clone(&get_created);
+            async move {
+                handler_calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                if get_created.load(std::sync::atomic::Ordering::SeqCst) {
+                    (StatusCode::OK, r#"{"a":1}"#)
+                } else {
+                    (StatusCode::NOT_FOUND, "")
+                }
+            }
+        }).put(move || {
+            let put_created = std::sync::Arc::clone(&put_created);
+            async move {
+                put_created.store(true, std::sync::atomic::Ordering::SeqCst);
+                r#"{"a":1}"#
+            }
+        }))
+        .route("/posts_missing", post(|| async {
+            (StatusCode::CREATED, [("Location", "/posts_missing/2")], r#"{"a":2}"#)
+        }))
+        .layer(from_fn_with_state(manager.get_state(), axum_redis_cache::middleware));
+    let send_to = |method: &str, uri: &str| {
+        app.clone().oneshot(Request::builder().method(method).uri(uri).body(Body::from(r#"{"a":1}"#)).unwrap())
+    };
+    let send = |method: &str| send_to(method, "/posts_missing/1");
+
+    // (1) 404는 missing 마커로 기억, 두 번째 GET은 핸들러 호출 없음
+    assert_eq!(send("GET").await.unwrap().status(), StatusCode::NOT_FOUND);
+    let response = send("GET").await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.headers()["X-Cache"], "HIT");
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+    let ttl: i64 = cache.conn.ttl("missing:posts_missing:1").await.unwrap();
+    assert!(ttl > 0 && ttl <= 2);
+
+    // (2) PUT(생성) → 마커 삭제, 이후 GET은 새 값
+    assert_eq!(send("PUT").await.unwrap().status(), StatusCode::OK);
+    assert!(!cache.conn.exists::<_, bool>("missing:posts_missing:1").await.unwrap());
+    assert_eq!(send("GET").await.unwrap().status(), StatusCode::OK);
+
+    // (3) 컬렉션 POST(201 + Location) → 생성된 id의 마커 삭제
+    created.store(false, std::sync::atomic::Ordering::SeqCst);
+    assert_eq!(send_to("GET", "/posts_missing/2").await.unwrap().status(), StatusCode::NOT_FOUND);
+    assert!(cache.conn.exists::<_, bool>("missing:posts_missing:2").await.unwrap());
+    assert_eq!(send_to("POST", "/posts_missing").await.unwrap().status(), StatusCode::CREATED);
+    assert!(!cache.conn.exists::<_, bool>("missing:posts_missing:2").await.unwrap());
+
+    // (4) HTTP 밖에서 생성된 경우 명시적으로 마커 삭제
+    assert_eq!(send_to("GET", "/posts_missing/3").await.unwrap().status(), StatusCode::NOT_FOUND);
+    manager.forget_missing("3").await.unwrap();
+    assert!(!cache.conn.exists::<_, bool>("missing:posts_missing:3").await.unwrap());
+
+    manager.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_cache_ttl_policy() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url);
+    let mut cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+    let mut manager = cache.get_manager(
+        "posts_policy".to_string(),
+        |_db, _s| async {},
+        |_db, _s| async {},
+        common::merge_json,
+    );
+
+    // 핸들러가 헤더 / extension 으로 TTL 지정
+    let app = Router::new()
+        .route("/posts_policy/1", get(|| async {
+            ([(axum_redis_cache::CACHE_TTL_HEADER, "300")], r#"{"a":1}"#)
+        }))
+        .route("/posts_policy/2", get(|| async {
+            (axum::Extension(axum_redis_cache::CacheTtl(600)), r#"{"a":2}"#)
+        }))
+        .route("/posts_policy/3", get(|| async { r#"{"a":3}"# }))
+        .route("/posts_policy/4", get(|| async {
+            ([(axum_redis_cache::CACHE_TTL_HEADER, "0")], r#"{"a":4}"#)
+        }))
+        .route("/posts_policy/5", get(|| async {
+            (axum::Extension(axum_redis_cache::CacheTtl(u64::MAX)), r#"{"a":5}"#)
+        }))
+        .layer(from_fn_with_state(manager.get_state(), axum_redis_cache::middleware));
+    let get_id = |id: u32| {
+        app.clone().oneshot(Request::builder().uri(format!("/posts_policy/{id}")).body(Body::empty()).unwrap())
+    };
+
+    // (1) 기본 정책: 헤더, extension, 없으면 ttl_clean
+    for id in 1..=3 {
+        assert_eq!(get_id(id).await.unwrap().status(), StatusCode::OK);
+    }
+    let ttl: i64 = cache.conn.ttl("posts_policy:1").await.unwrap();
+    assert!(ttl > 290 && ttl <= 300);
+    let ttl: i64 = cache.conn.ttl("posts_policy:2").await.unwrap();
+    assert!(ttl > 590 && ttl <= 600);
+    let ttl: i64 = cache.conn.ttl("posts_policy:3").await.unwrap();
+    assert!(ttl > 50 && ttl <= 60);
+    // 항목마다 저장 당시 TTL 기록 (fresh:expiry)
+    let stored: String = cache.conn.get("ttl:posts_policy:1").await.unwrap();
+    assert_eq!(stored, "300:300");
+
+    // (2) TTL 0 → 응답은 그대로, 캐시하지 않음 / 너무 큰 TTL → MAX_TTL 로 제한
+    let response = get_id(4).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&body[..], br#"{"a":4}"#);
+    assert!(!cache.conn.exists::<_, bool>("posts_policy:4").await.unwrap());
+    assert_eq!(get_id(5).await.unwrap().status(), StatusCode::OK);
+    let ttl: i64 = cache.conn.ttl("posts_policy:5").await.unwrap();
+    assert!(ttl > 0 && ttl as u64 <= axum_redis_cache::MAX_TTL);
+
+    // (3) 엔티티 키 기준 클로저 정책으로 교체
+    manager = manager.with_ttl_policy(|ctx: &axum_redis_cache::TtlContext| {
+        (ctx.key == "posts_policy:3").then_some(900)
+    });
+    let _: () = cache.conn.del("posts_policy:3").await.unwrap();
+    assert_eq!(get_id(3).await.unwrap().status(), StatusCode::OK);
+    let ttl: i64 = cache.conn.ttl("posts_policy:3").await.unwrap();
+    assert!(ttl > 890 && ttl <= 900);
+
+    manager.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_cache_ttl_jitter() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url);
+    let mut cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+    let mut manager = cache.get_manager(
+        "posts_jitter".to_string(),
+        |_db, _s| async {},
+        |_db, _s| async {},
+        common::merge_json,
+    ).with_config(CacheConfig::new().with_clean_ttl(1000).with_ttl_jitter(50));
+
+    let app = Router::new()
+        .route("/posts_jitter/:id", get(|| async { r#"{"a":1}"# }))
+        .layer(from_fn_with_state(manager.get_state(), axum_redis_cache::middleware));
+
+    // 같은 시점에 채운 엔트리도 TTL 이 500~1000 초 사이로 흩어짐
+    let mut ttls = Vec::new();
+    for id in 1..=20 {
+        let response = app.clone()
+            .oneshot(Request::builder().uri(format!("/posts_jitter/{id}")).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let ttl: i64 = cache.conn.ttl(format!("posts_jitter:{id}")).await.unwrap();
+        assert!((499..=1000).contains(&ttl), "ttl {ttl} out of jitter range");
+        ttls.push(ttl);
+    }
+    ttls.sort();
+    ttls.dedup();
+    assert!(ttls.len() > 1);
+
+    manager.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_cache_size_limits() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url);
+    let mut cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+    let mut manager = cache.get_manager(
+        "posts_big".to_string(),
+        |_db, _s| async {},
+        |_db, _s| async {},
+        common::merge_json,
+    ).with_config(CacheConfig::new().with_max_body_size(64).with_max_request_size(32));
+
+    // 1: 길이 헤더 있는 큰 응답, 2: 길이 모르는 스트림 응답, 3: 작은 응답
+    let big = format!(r#"{{"a":"{}"}}"#, "x".repeat(100));
+    let (big_1, big_2) = (big.clone(), big.clone());
+    let app = Router::new()
+        .route("/posts_big/1", get(move || async move { big_1 }))
+        .route("/posts_big/2", get(move || async move {
+            let chunks = big_2.into_bytes().chunks(10).map(|c| Ok::<_, std::io::Error>(c.to_vec())).collect::<Vec<_>>();
+            Body::from_stream(futures_util::stream::iter(chunks))
+        }))
+        .route("/posts_big/3", get(|| async { r#"{"a":1}"# }).put(|| async { r#"{"a":1}"# }))
+        .layer(from_fn_with_state(manager.get_state(), axum_redis_cache::middleware));
+    let send = |method: &str, uri: &str, body: Body| {
+        app.clone().oneshot(Request::builder().method(method).uri(uri).body(body).unwrap())
+    };
+
+    // (1) 제한을 넘는 응답은 그대로 전달, 캐시 안 됨
+    for id in 1..=2 {
+        let response = send("GET", &format!("/posts_big/{id}"), Body::empty()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(bytes, big.as_bytes());
+        assert!(!cache.conn.exists::<_, bool>(format!("posts_big:{id}")).await.unwrap());
+    }
+
+    // (2) 작은 응답은 캐시
+    assert_eq!(send("GET", "/posts_big/3", Body::empty()).await.unwrap().status(), StatusCode::OK);
+    assert!(cache.conn.exists::<_, bool>("posts_big:3").await.unwrap());
+
+    // (3) 제한을 넘는 PUT 본문은 병합 전에 413
+    let response = send("PUT", "/posts_big/3", Body::from(big.clone())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(!cache.conn.exists::<_, bool>("dirty:posts_big:3").await.unwrap());
+    assert_eq!(send("PUT", "/posts_big/3", Body::from(r#"{"a":2}"#)).await.unwrap().status(), StatusCode::OK);
+
+    manager.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_cache_eviction_protection() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    // (1) allkeys-* 정책이면 Refuse 설정은 시작을 거부
+    let mut admin = redis::Client::open(redis_url.as_str()).unwrap().get_multiplexed_async_connection().await.unwrap();
+    let _: () = redis::cmd("CONFIG").arg("SET").arg("maxmemory-policy").arg("allkeys-lru")
+        .query_async(&mut admin).await.unwrap();
+    let config = CacheConnConfig::new()
+        .with_url(&redis_url)
+        .with_eviction_check(axum_redis_cache::EvictionCheck::Refuse);
+    let refused = CacheConnection::try_new_with_config(pool.clone(), config).await;
+    let error = refused.err().expect("allkeys-lru must be refused");
+    assert!(error.to_string().contains("allkeys-lru"));
+
+    // (2) 안전한 정책에서는 시작, dirty 키 eviction 은 콜백과 카운터로 보고
+    let _: () = redis::cmd("CONFIG").arg("SET").arg("maxmemory-policy").arg("volatile-lru")
+        .query_async(&mut admin).await.unwrap();
+    let lost = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let reported = std::sync::Arc::clone(&lost);
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url)
+        .with_eviction_check(axum_redis_cache::EvictionCheck::Refuse)
+        .with_on_dirty_evicted(move |key| reported.lock().unwrap().push(key.to_string()));
+    let cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+    let mut manager = cache.get_manager(
+        "posts_evict".to_string(),
+        |_db, _s| async {},
+        |_db, _s| async {},
+        common::merge_json,
+    );
+    sleep(Duration::from_millis(300)).await;
+
+    // 실제 eviction 대신 keyevent 를 직접 발행 (clean 키는 보고 안 함)
+    for key in ["posts_evict:2", "dirty:posts_evict:1"] {
+        let _: i64 = admin.publish("__keyevent@0__:evicted", key).await.unwrap();
+    }
+    sleep(Duration::from_millis(300)).await;
+    assert_eq!(*lost.lock().unwrap(), vec!["posts_evict:1".to_string()]);
+    assert_eq!(manager.conn.evicted_dirty_count(), 1);
+
+    manager.shutdown().await;
+}
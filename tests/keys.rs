@@ -0,0 +1,72 @@
+// tests/keys.rs
+
+use axum_redis_cache::KeySpace;
+
+#[test]
+fn key_space_default_layout() {
+    let keys = KeySpace::default();
+    assert_eq!(keys.clean("posts:1"), "posts:1");
+    assert_eq!(keys.dirty("posts:1"), "dirty:posts:1");
+    assert_eq!(keys.delete("posts:1"), "delete:posts:1");
+    assert_eq!(keys.lock("posts:1"), "lock:posts:1");
+    assert_eq!(keys.missing("posts:1"), "missing:posts:1");
+    assert_eq!(keys.ttl("posts:1"), "ttl:posts:1");
+    assert_eq!(keys.dirty_pattern("posts"), "dirty:posts:*");
+    assert_eq!(keys.clean_from_dirty("dirty:posts:1").as_deref(), Some("posts:1"));
+    assert_eq!(keys.version("posts:1"), "dirty-version:posts:1");
+    assert_eq!(keys.version_from_dirty("dirty:posts:1").as_deref(), Some("dirty-version:posts:1"));
+    assert_eq!(keys.delete_from_dirty("dirty:posts:1").as_deref(), Some("delete:posts:1"));
+    assert_eq!(keys.key_from_dirty("dirty:posts:1").as_deref(), Some("posts:1"));
+    assert_eq!(keys.delete_id("posts", "delete:posts:1"), Some("1"));
+    assert_eq!(keys.delete_id("posts", "delete:comments:1"), None);
+    assert_eq!(keys.delete_queue("posts"), "delete-queue:posts");
+    assert_eq!(keys.delete_stream("posts"), "delete-stream:posts");
+    assert_eq!(keys.dirty_stream("posts"), "dirty-stream:posts");
+    assert_eq!(keys.leader("posts"), "leader:posts");
+    assert_eq!(keys.leader_token("posts"), "leader-token:posts");
+    assert_eq!(keys.invalidation_channel(), "l1-invalidate");
+}
+
+#[test]
+fn key_space_hash_tags_share_slot() {
+    let keys = KeySpace {
+        prefix: "myapp:".to_string(),
+        hash_tags: true,
+        ..KeySpace::default()
+    };
+    assert_eq!(keys.clean("posts:1"), "myapp:{posts:1}");
+    assert_eq!(keys.dirty("posts:1"), "myapp:dirty:{posts:1}");
+    assert_eq!(keys.delete("posts:1"), "myapp:delete:{posts:1}");
+    assert_eq!(keys.lock("posts:1"), "myapp:lock:{posts:1}");
+    assert_eq!(keys.missing("posts:1"), "myapp:missing:{posts:1}");
+    assert_eq!(keys.ttl("posts:1"), "myapp:ttl:{posts:1}");
+    assert_eq!(keys.dirty_pattern("posts"), "myapp:dirty:{posts:*");
+    assert_eq!(keys.clean_from_dirty("myapp:dirty:{posts:1}").as_deref(), Some("myapp:{posts:1}"));
+    assert_eq!(keys.version_from_dirty("myapp:dirty:{posts:1}").as_deref(), Some("myapp:dirty-version:{posts:1}"));
+    assert_eq!(keys.delete_from_dirty("myapp:dirty:{posts:1}").as_deref(), Some("myapp:delete:{posts:1}"));
+    assert_eq!(keys.key_from_dirty("myapp:dirty:{posts:1}").as_deref(), Some("posts:1"));
+    assert_eq!(keys.delete_id("posts", "myapp:delete:{posts:1}"), Some("1"));
+    assert_eq!(keys.delete_queue("posts"), "myapp:delete-queue:{posts}");
+    assert_eq!(keys.delete_stream("posts"), "myapp:delete-stream:{posts}");
+    assert_eq!(keys.dirty_stream("posts"), "myapp:dirty-stream:{posts}");
+    assert_eq!(keys.leader("posts"), "myapp:leader:{posts}");
+    assert_eq!(keys.leader_token("posts"), "myapp:leader-token:{posts}");
+    assert_eq!(keys.invalidation_channel(), "myapp:l1-invalidate");
+}
+
+#[test]
+fn key_space_custom_markers() {
+    let keys = KeySpace {
+        prefix: "myapp:".to_string(),
+        lock: "mutex".to_string(),
+        missing: "absent".to_string(),
+        leader: "owner".to_string(),
+        invalidation: "evict-local".to_string(),
+        ..KeySpace::default()
+    };
+    assert_eq!(keys.lock("posts:1"), "myapp:mutex:posts:1");
+    assert_eq!(keys.missing("posts:1"), "myapp:absent:posts:1");
+    assert_eq!(keys.leader("posts"), "myapp:owner:posts");
+    assert_eq!(keys.leader_token("posts"), "myapp:owner-token:posts");
+    assert_eq!(keys.invalidation_channel(), "myapp:evict-local");
+}
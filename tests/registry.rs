@@ -0,0 +1,121 @@
+// tests/registry.rs
+
+use axum::{
+    Router,
+    routing::get,
+    body::Body,
+    http::{Request, StatusCode},
+    middleware::from_fn_with_state,
+};
+use tower::ServiceExt;
+use axum_redis_cache::{CacheConnection, CacheConfig, CacheConnConfig, CacheResource};
+use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+use std::time::Duration;
+use tokio::time::sleep;
+use redis::AsyncCommands;
+
+#[path = "common.rs"]
+#[allow(unused_imports, unused_mut, dead_code)]
+mod common;
+
+#[tokio::test]
+async fn test_registry_dispatch_and_flush() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url);
+    let mut cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+
+    let posts_written = Arc::new(AtomicUsize::new(0));
+    let comments_written = Arc::new(AtomicUsize::new(0));
+    let posts_counter = Arc::clone(&posts_written);
+    let comments_counter = Arc::clone(&comments_written);
+
+    let mut registry = cache
+        .get_registry()
+        .register(CacheResource::new(
+            "posts",
+            "/posts/:id",
+            move |_db, _s| {
+                let counter = Arc::clone(&posts_counter);
+                async move { counter.fetch_add(1, Ordering::SeqCst); }
+            },
+            |_db, _s| async {},
+            common::merge_json,
+        ).with_config(CacheConfig::new().with_write_duration(1)))
+        .register(CacheResource::new(
+            "comments",
+            "/comments/{id}",
+            move |_db, _s| {
+                let counter = Arc::clone(&comments_counter);
+                async move { counter.fetch_add(1, Ordering::SeqCst); }
+            },
+            |_db, _s| async {},
+            common::merge_json,
+        ).with_config(CacheConfig::new().with_write_duration(1)))
+        .start();
+
+    let app = Router::new()
+        .route("/posts/:id", get(|| async { "post" }).put(|| async { "post" }))
+        .route("/comments/:id", get(|| async { "comment" }).put(|| async { "comment" }))
+        .route("/users/:id", get(|| async { "user" }))
+        .layer(from_fn_with_state(registry.get_state(), axum_redis_cache::registry_middleware));
+
+    // (1) 등록된 리소스는 각각 캐시됨, 등록되지 않은 경로는 통과
+    for uri in ["/posts/1", "/comments/1", "/users/1"] {
+        let response = app
+            .clone()
+            .oneshot(Request::builder().method("GET").uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    assert!(cache.conn.exists::<_, bool>("posts:1").await.unwrap());
+    assert!(cache.conn.exists::<_, bool>("comments:1").await.unwrap());
+    assert!(!cache.conn.exists::<_, bool>("users:1").await.unwrap());
+
+    // (2) PUT → 두 리소스 모두 dirty
+    for uri in ["/posts/1", "/comments/1"] {
+        let response = app
+            .clone()
+            .oneshot(Request::builder().method("PUT").uri(uri).body(Body::from(r#"{"a":1}"#)).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    // (3) 공유 write-behind 스케줄러가 두 리소스를 모두 flush
+    sleep(Duration::from_secs(3)).await;
+    assert_eq!(posts_written.load(Ordering::SeqCst), 1);
+    assert_eq!(comments_written.load(Ordering::SeqCst), 1);
+    assert!(!cache.conn.exists::<_, bool>("dirty:posts:1").await.unwrap());
+    assert!(!cache.conn.exists::<_, bool>("dirty:comments:1").await.unwrap());
+
+    registry.shutdown().await;
+}
+
+#[tokio::test]
+#[should_panic(expected = "is not under cache key")]
+async fn test_registry_rejects_route_outside_key() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_url(&redis_url);
+    let cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+
+    let _builder = cache.get_registry().register(CacheResource::new(
+        "posts",
+        "/comments/:id",
+        |_db, _s| async {},
+        |_db, _s| async {},
+        common::merge_json,
+    ));
+}
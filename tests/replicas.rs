@@ -0,0 +1,327 @@
+// tests/replicas.rs
+
+use axum::{
+    Router,
+    routing::get,
+    body::Body,
+    http::{Request, StatusCode},
+    middleware::from_fn_with_state,
+};
+use tower::ServiceExt;
+use axum_redis_cache::{CacheConnection, CacheConfig, CacheConnConfig, CacheManager, SingleFlight, WriteBehind};
+use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+use std::time::Duration;
+use tokio::time::sleep;
+use redis::AsyncCommands;
+
+#[path = "common.rs"]
+#[allow(unused_imports, unused_mut, dead_code)]
+mod common;
+
+/// 같은 리소스를 관리하는 replica 하나 (write 횟수 공유 카운터)
+fn replica<DB: sqlx::Database>(cache: &CacheConnection<DB>, written: &Arc<AtomicUsize>) -> CacheManager<DB> {
+    let counter = Arc::clone(written);
+    cache.get_manager(
+        "posts_shared".to_string(),
+        move |_db, _s| {
+            let counter = Arc::clone(&counter);
+            async move { counter.fetch_add(1, Ordering::SeqCst); }
+        },
+        |_db, _s| async {},
+        common::merge_json,
+    ).with_config(CacheConfig::new().with_write_duration(1))
+}
+
+async fn send_get(app: &Router, uri: &str) {
+    let response = app
+        .clone()
+        .oneshot(Request::builder().method("GET").uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+async fn send_put(app: &Router, uri: &str) {
+    let response = app
+        .clone()
+        .oneshot(Request::builder().method("PUT").uri(uri).body(Body::from(r#"{"a":1}"#)).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_replicas_flush_once_and_fail_over() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let cache_a = CacheConnection::new_with_config(pool.clone(), CacheConnConfig::new().with_url(&redis_url)).await;
+    let mut cache_b = CacheConnection::new_with_config(pool.clone(), CacheConnConfig::new().with_url(&redis_url)).await;
+
+    let written = Arc::new(AtomicUsize::new(0));
+    let mut replica_a = replica(&cache_a, &written);
+    let mut replica_b = replica(&cache_b, &written);
+
+    let app_a = Router::new()
+        .route("/posts_shared/:id", get(|| async { r#"{"a":0}"# }).put(|| async { "updated" }))
+        .layer(from_fn_with_state(replica_a.get_state(), axum_redis_cache::middleware));
+    let app_b = Router::new()
+        .route("/posts_shared/:id", get(|| async { r#"{"a":0}"# }).put(|| async { "updated" }))
+        .layer(from_fn_with_state(replica_b.get_state(), axum_redis_cache::middleware));
+
+    // (1) 두 replica 모두 write-behind를 돌리지만 리더만 flush → 항목당 DB write 1번
+    // (GET으로 캐시를 채워야 PUT이 dirty 항목이 됨)
+    for id in 1..=3 {
+        let uri = format!("/posts_shared/{id}");
+        send_get(&app_a, &uri).await;
+        send_put(&app_a, &uri).await;
+    }
+    sleep(Duration::from_secs(3)).await;
+    assert_eq!(written.load(Ordering::SeqCst), 3);
+
+    let lease: String = cache_b.conn.get("leader:posts_shared").await.unwrap();
+    let token: u64 = cache_b.conn.get("leader-token:posts_shared").await.unwrap();
+    assert!(lease.ends_with(&format!(":{token}")));
+
+    // (2) 한 replica 종료 → lease 반납, 남은 replica가 새 fencing token으로 이어받음
+    replica_a.shutdown().await;
+    send_get(&app_b, "/posts_shared/4").await;
+    send_put(&app_b, "/posts_shared/4").await;
+    sleep(Duration::from_secs(4)).await;
+    assert_eq!(written.load(Ordering::SeqCst), 4);
+    let next_token: u64 = cache_b.conn.get("leader-token:posts_shared").await.unwrap();
+    assert!(next_token >= token);
+
+    replica_b.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_stream_write_behind_shared_by_replicas() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let mut cache_a = CacheConnection::new_with_config(pool.clone(), CacheConnConfig::new().with_url(&redis_url)).await;
+    let cache_b = CacheConnection::new_with_config(pool.clone(), CacheConnConfig::new().with_url(&redis_url)).await;
+
+    let written = Arc::new(AtomicUsize::new(0));
+    let stream_config = CacheConfig::new()
+        .with_write_duration(60)
+        .with_write_behind(WriteBehind::Stream { workers: 2 });
+    let mut replica_a = replica(&cache_a, &written).with_config(stream_config);
+    let mut replica_b = replica(&cache_b, &written).with_config(stream_config);
+
+    let app = Router::new()
+        .route("/posts_shared/:id", get(|| async { r#"{"a":0}"# }).put(|| async { "updated" }))
+        .layer(from_fn_with_state(replica_a.get_state(), axum_redis_cache::middleware));
+
+    // (1) GET으로 캐시 채운 뒤 PUT → dirty + 스트림 이벤트
+    for id in 1..=4 {
+        let uri = format!("/posts_shared/{id}");
+        send_get(&app, &uri).await;
+        send_put(&app, &uri).await;
+    }
+
+    // (2) write_duration(60초)을 기다리지 않고 consumer group이 처리, 이벤트당 1번 write
+    sleep(Duration::from_secs(2)).await;
+    assert_eq!(written.load(Ordering::SeqCst), 4);
+    let dirty: Vec<String> = cache_a.conn.keys("dirty:posts_shared:*").await.unwrap();
+    assert!(dirty.is_empty());
+    let pending: redis::streams::StreamPendingReply = cache_a.conn
+        .xpending("dirty-stream:posts_shared", "cache-write")
+        .await
+        .unwrap();
+    assert_eq!(pending.count(), 0);
+
+    replica_a.shutdown().await;
+    replica_b.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_single_flight_distributed_across_replicas() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let mut cache_a = CacheConnection::new_with_config(pool.clone(), CacheConnConfig::new().with_url(&redis_url)).await;
+    let cache_b = CacheConnection::new_with_config(pool.clone(), CacheConnConfig::new().with_url(&redis_url)).await;
+
+    let written = Arc::new(AtomicUsize::new(0));
+    let config = CacheConfig::new()
+        .with_single_flight(SingleFlight::Distributed { wait: Duration::from_secs(2) });
+    let mut replica_a = replica(&cache_a, &written).with_config(config);
+    let mut replica_b = replica(&cache_b, &written).with_config(config);
+
+    // 두 replica가 같은 느린 핸들러를 공유 (호출 횟수 기록)
+    let calls = Arc::new(AtomicUsize::new(0));
+    let app = |state| {
+        let calls = Arc::clone(&calls);
+        Router::new()
+            .route("/posts_shared/:id", get(move || {
+                let calls = Arc::clone(&calls);
+                async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    sleep(Duration::from_millis(500)).await;
+                    r#"{"a":0}"#
+                }
+            }))
+            .layer(from_fn_with_state(state, axum_redis_cache::middleware))
+    };
+    let app_a = app(replica_a.get_state());
+    let app_b = app(replica_b.get_state());
+
+    // replica마다 동시 miss 5개 → 전체에서 핸들러 1번
+    let gets = (0..10).map(|i| {
+        let app = if i % 2 == 0 { &app_a } else { &app_b };
+        app.clone().oneshot(Request::builder().method("GET").uri("/posts_shared/1").body(Body::empty()).unwrap())
+    });
+    for response in futures_util::future::join_all(gets).await {
+        assert_eq!(response.unwrap().status(), StatusCode::OK);
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert!(!cache_a.conn.exists::<_, bool>("lock:posts_shared:1").await.unwrap());
+
+    replica_a.shutdown().await;
+    replica_b.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_l1_invalidated_across_replicas() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let mut cache_a = CacheConnection::new_with_config(pool.clone(), CacheConnConfig::new().with_url(&redis_url)).await;
+    let cache_b = CacheConnection::new_with_config(pool.clone(), CacheConnConfig::new().with_url(&redis_url)).await;
+
+    let written = Arc::new(AtomicUsize::new(0));
+    let config = CacheConfig::new()
+        .with_write_duration(60)
+        .with_l1(100, Duration::from_secs(30));
+    let mut replica_a = replica(&cache_a, &written).with_config(config);
+    let mut replica_b = replica(&cache_b, &written).with_config(config);
+
+    let app = |state| {
+        Router::new()
+            .route("/posts_shared/:id", get(|| async { r#"{"a":0}"# }).put(|| async { r#"{"a":1}"# }))
+            .layer(from_fn_with_state(state, axum_redis_cache::middleware))
+    };
+    let app_a = app(replica_a.get_state());
+    let app_b = app(replica_b.get_state());
+    let get_body = |app: &Router| {
+        let app = app.clone();
+        async move {
+            let response = app
+                .oneshot(Request::builder().uri("/posts_shared/1").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            String::from_utf8(bytes.to_vec()).unwrap()
+        }
+    };
+
+    // (1) miss → Redis, 두 번째 GET부터 각 replica의 L1에 저장
+    get_body(&app_a).await;
+    get_body(&app_a).await;
+    get_body(&app_b).await;
+
+    // (2) Redis 값을 몰래 바꿔도 L1 값이 응답됨
+    let _: () = redis::cmd("SET").arg("posts_shared:1").arg(r#"{"a":9}"#).arg("KEEPTTL")
+        .query_async(&mut cache_a.conn).await.unwrap();
+    assert_eq!(get_body(&app_a).await, r#"{"a":0}"#);
+    assert_eq!(get_body(&app_b).await, r#"{"a":0}"#);
+
+    // (3) A의 PUT → A, B 모두 L1 무효화 후 dirty 값을 읽음
+    send_put(&app_a, "/posts_shared/1").await;
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(get_body(&app_a).await, r#"{"a":1}"#);
+    assert_eq!(get_body(&app_b).await, r#"{"a":1}"#);
+
+    replica_a.shutdown().await;
+    replica_b.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_flush_stops_after_losing_lease() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let mut cache = CacheConnection::new_with_config(pool.clone(), CacheConnConfig::new().with_url(&redis_url)).await;
+
+    // 느린 DB write (키당 300ms)
+    let written = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&written);
+    let mut manager = cache.get_manager(
+        "posts_fenced".to_string(),
+        move |_db, _s| {
+            let counter = Arc::clone(&counter);
+            async move {
+                sleep(Duration::from_millis(300)).await;
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        },
+        |_db, _s| async {},
+        common::merge_json,
+    ).with_config(CacheConfig::new().with_write_duration(1));
+
+    for id in 1..=20 {
+        let _: () = cache.conn.set(format!("dirty:posts_fenced:{id}"), r#"{"a":1}"#).await.unwrap();
+    }
+
+    // (1) flush 도중 다른 replica가 lease를 가져감
+    sleep(Duration::from_millis(1500)).await;
+    let _: () = redis::cmd("SET").arg("leader:posts_fenced").arg("other:999").arg("PX").arg(60_000)
+        .query_async(&mut cache.conn).await.unwrap();
+
+    // (2) 다음 lease 확인(최대 3초) 후 flush 중단, 남은 dirty 키는 그대로
+    sleep(Duration::from_secs(4)).await;
+    let stopped_at = written.load(Ordering::SeqCst);
+    sleep(Duration::from_secs(2)).await;
+    assert_eq!(written.load(Ordering::SeqCst), stopped_at);
+    assert!(stopped_at < 20);
+    let dirty: Vec<String> = cache.conn.keys("dirty:posts_fenced:*").await.unwrap();
+    assert_eq!(dirty.len(), 20 - stopped_at);
+
+    manager.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_stream_write_behind_sweeps_entries_without_event() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    let redisstruct = common::start_redis().await;
+    let redis_url = redisstruct.url;
+
+    let mut cache = CacheConnection::new_with_config(pool.clone(), CacheConnConfig::new().with_url(&redis_url)).await;
+
+    let written = Arc::new(AtomicUsize::new(0));
+    let stream_config = CacheConfig::new()
+        .with_write_duration(60)
+        .with_write_behind(WriteBehind::Stream { workers: 1 })
+        .with_reclaim_idle(Duration::from_secs(1));
+    let mut manager = replica(&cache, &written).with_config(stream_config);
+
+    // 스트림 이벤트 없이 남은 dirty 항목 (XADD 실패 상황)
+    let _: () = cache.conn.set("dirty:posts_shared:1", r#"{"a":1}"#).await.unwrap();
+
+    // reclaim_idle 주기의 sweep 으로 flush
+    sleep(Duration::from_secs(3)).await;
+    assert_eq!(written.load(Ordering::SeqCst), 1);
+    assert!(!cache.conn.exists::<_, bool>("dirty:posts_shared:1").await.unwrap());
+
+    manager.shutdown().await;
+}
@@ -0,0 +1,146 @@
+// tests/sentinel.rs
+#![cfg(feature = "sentinel")]
+
+use axum::{
+    Router,
+    routing::put,
+    body::Body,
+    http::{Request, StatusCode},
+    middleware::from_fn_with_state,
+};
+use tower::ServiceExt;
+use axum_redis_cache::{CacheConnection, CacheConnConfig, SentinelConfig};
+use std::net::IpAddr;
+use std::time::Duration;
+use testcontainers::{
+    core::WaitFor,
+    runners::AsyncRunner,
+    ContainerAsync,
+    GenericImage,
+    ImageExt,
+};
+use tokio::time::sleep;
+use redis::AsyncCommands;
+
+#[path = "common.rs"]
+#[allow(unused_imports, unused_mut, dead_code)]
+mod common;
+
+const MASTER_NAME: &str = "mymaster";
+
+/// redis-server 컨테이너 (`args` 는 추가 인자, ex: --replicaof)
+async fn start_server(args: &[String]) -> (ContainerAsync<GenericImage>, IpAddr) {
+    let mut cmd = vec!["redis-server".to_string()];
+    cmd.extend_from_slice(args);
+    let container = GenericImage::new("redis", "7.2")
+        .with_wait_for(WaitFor::message_on_stdout("Ready to accept connections"))
+        .with_cmd(cmd)
+        .start()
+        .await
+        .unwrap();
+    let ip = container.get_bridge_ip_address().await.unwrap();
+    (container, ip)
+}
+
+/// master 를 감시하는 sentinel 컨테이너 (quorum 1, 빠른 failover)
+async fn start_sentinel(master: IpAddr) -> (ContainerAsync<GenericImage>, IpAddr) {
+    let script = format!(
+        "printf 'port 26379\\nsentinel monitor {MASTER_NAME} {master} 6379 1\\n\
+         sentinel down-after-milliseconds {MASTER_NAME} 1000\\n\
+         sentinel failover-timeout {MASTER_NAME} 5000\\n' > /tmp/sentinel.conf \
+         && redis-sentinel /tmp/sentinel.conf"
+    );
+    let container = GenericImage::new("redis", "7.2")
+        .with_wait_for(WaitFor::message_on_stdout("+monitor master"))
+        .with_entrypoint("sh")
+        .with_cmd(vec!["-c".to_string(), script])
+        .start()
+        .await
+        .unwrap();
+    let ip = container.get_bridge_ip_address().await.unwrap();
+    (container, ip)
+}
+
+/// sentinel 이 알려주는 현재 master IP
+async fn master_ip(sentinel: &mut redis::aio::MultiplexedConnection) -> String {
+    let (ip, _port): (String, String) = redis::cmd("SENTINEL")
+        .arg("get-master-addr-by-name")
+        .arg(MASTER_NAME)
+        .query_async(sentinel)
+        .await
+        .unwrap();
+    ip
+}
+
+async fn send_put(app: &Router, uri: &str) -> StatusCode {
+    app.clone()
+        .oneshot(Request::builder().method("PUT").uri(uri).body(Body::from(r#"{"a":1}"#)).unwrap())
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn test_sentinel_resolves_master_and_follows_failover() {
+    let pgstruct  = common::start_postgres().await;
+    let pool = pgstruct.pool;
+
+    // master + replica + sentinel (컨테이너 IP 로 직접 접속)
+    let (_master, master) = start_server(&[]).await;
+    let (_replica, replica) = start_server(&["--replicaof".to_string(), master.to_string(), "6379".to_string()]).await;
+    let (_sentinel, sentinel) = start_sentinel(master).await;
+
+    let sentinel_url = format!("redis://{sentinel}:26379");
+    let mut sentinel_conn = redis::Client::open(sentinel_url.as_str()).unwrap()
+        .get_multiplexed_async_connection().await.unwrap();
+    let mut replica_conn = redis::Client::open(format!("redis://{replica}:6379")).unwrap()
+        .get_multiplexed_async_connection().await.unwrap();
+
+    let cache_conn_config = CacheConnConfig::new()
+        .with_sentinel(SentinelConfig::new(MASTER_NAME, &[&sentinel_url]));
+    let cache = CacheConnection::new_with_config(pool.clone(), cache_conn_config).await;
+
+    // (1) sentinel 을 통해 master 로 접속
+    let client = cache.conn.current_client().unwrap();
+    assert_eq!(client.get_connection_info().addr.to_string(), format!("{master}:6379"));
+
+    let mut manager = cache.get_manager(
+        "posts_sentinel".to_string(),
+        |_db, _s| async {},
+        |_db, _s| async {},
+        common::merge_json,
+    );
+    let app = Router::new()
+        .route("/posts_sentinel/:id", put(|| async { r#"{"a":1}"# }))
+        .layer(from_fn_with_state(manager.get_state(), axum_redis_cache::middleware));
+    assert_eq!(send_put(&app, "/posts_sentinel/1").await, StatusCode::OK);
+
+    // (2) replica 로 failover
+    let _: () = redis::cmd("SENTINEL").arg("FAILOVER").arg(MASTER_NAME)
+        .query_async(&mut sentinel_conn).await.unwrap();
+    for _ in 0..100 {
+        if master_ip(&mut sentinel_conn).await == replica.to_string() {
+            break;
+        }
+        sleep(Duration::from_millis(200)).await;
+    }
+    assert_eq!(master_ip(&mut sentinel_conn).await, replica.to_string());
+
+    // (3) 옛 master 의 READONLY / 끊김 → 새 master 로 재접속 후 쓰기 성공
+    let mut status = StatusCode::INTERNAL_SERVER_ERROR;
+    for _ in 0..20 {
+        status = send_put(&app, "/posts_sentinel/2").await;
+        if status == StatusCode::OK {
+            break;
+        }
+        sleep(Duration::from_millis(500)).await;
+    }
+    assert_eq!(status, StatusCode::OK);
+    assert!(manager.conn.reconnect_count() >= 1);
+    let client = manager.conn.current_client().unwrap();
+    assert_eq!(client.get_connection_info().addr.to_string(), format!("{replica}:6379"));
+    assert!(replica_conn.exists::<_, bool>("posts_sentinel:2").await.unwrap()
+        || replica_conn.exists::<_, bool>("dirty:posts_sentinel:2").await.unwrap());
+
+    manager.shutdown().await;
+}